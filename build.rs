@@ -0,0 +1,37 @@
+//! Selects the instructions available on the SiFive cores chosen by cargo features
+use std::env;
+
+const CORES: &[&str] = &[
+    "e20", "e21", "e24", "e31", "e34", "e76", "s21", "s51", "s54", "s76", "u54", "u74", "p270",
+    "p550", "x280",
+];
+
+// all SiFive Performance and Intelligence cores, and SiFive Essential U7, U5, S7 and E7 cores
+const FULL_CACHE: &[&str] = &["e76", "s76", "u54", "u74", "p270", "p550", "x280"];
+
+const CFLUSH_VA: &[&str] = &["e76", "s76", "p550"];
+
+fn main() {
+    let selected: Vec<&str> = CORES
+        .iter()
+        .copied()
+        .filter(|core| env::var_os(format!("CARGO_FEATURE_{}", core.to_uppercase())).is_some())
+        .collect();
+    // without any core selected, every instruction is available on RISC-V targets; other
+    // targets, e.g. hosts running unit tests, have none of them
+    let riscv = env::var("CARGO_CFG_TARGET_ARCH").is_ok_and(|arch| arch.starts_with("riscv"));
+    let supported = |cores: &[&str]| riscv && selected.iter().all(|core| cores.contains(core));
+    let instructions = [
+        ("cflush_d_l1_all", FULL_CACHE),
+        ("cflush_d_l1_va", CFLUSH_VA),
+        ("cdiscard_d_l1_all", FULL_CACHE),
+        ("cdiscard_d_l1_va", FULL_CACHE),
+    ];
+    for (name, cores) in instructions {
+        println!("cargo:rustc-check-cfg=cfg({})", name);
+        if supported(cores) {
+            println!("cargo:rustc-cfg={}", name);
+        }
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
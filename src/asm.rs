@@ -9,14 +9,164 @@
 //! On hardware implementation of SiFive platform, PAUSE instruction causes a stall of
 //! up to 32 cycles or until a cache eviction occurs, whichever comes first.
 //!
+//! For bounded busy waiting before timers are configured, [`pause_cycles`] combines PAUSE
+//! with reads of the `mcycle` counter.
+//!
+//! # Core support
+//!
+//! Cache control instructions are guarded at compile time rather than by runtime assertions.
+//! When core model cargo features are selected, see the [crate documentation](crate), an
+//! instruction not documented for all selected cores is not compiled, so calling e.g. an L1
+//! data cache flush when targeting a core without a data cache is a build error instead of an
+//! illegal-instruction exception. Neither debug nor release builds check the core model of
+//! current hart, as [`CoreModel`](crate::register::identity::CoreModel) does not decode every
+//! series; without core features all instructions are available, and platforms identifying
+//! the core at runtime use [`probe`](crate::probe::probe) with the fallible `try_*` wrappers.
+//!
+//! # Memory ordering
+//!
+//! Cache maintenance instructions in this module are emitted without the `nomem` and `readonly`
+//! options, so the compiler treats each of them as reading and writing memory: loads and stores
+//! written before a flush are not moved after it, and accesses written after an invalidation
+//! are not moved before it.
+//!
+//! This only constrains the compiler. Ordering against other harts and devices is achieved by
+//! FENCE instructions; use [`fence_before_flush`] and [`fence_after_invalidate`] around cache
+//! maintenance of buffers shared with DMA devices.
+//!
 //! [`core::hint::spin_loop()`]: https://doc.rust-lang.org/stable/core/hint/fn.spin_loop.html
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+use crate::nmi::NmiFrame;
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+use crate::probe::Support;
 use core::arch::asm;
+#[cfg(all(cflush_d_l1_va, cdiscard_d_l1_va))]
+use core::mem::MaybeUninit;
+
+/// L1 data cache line size
+///
+/// Range based cache operations use this value to round addresses to cache line boundaries.
+/// Associated constants give the line size of documented SiFive cores; use [`CacheLineSize::new`]
+/// for cores not listed here or customized configurations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CacheLineSize(usize);
+
+impl CacheLineSize {
+    /// SiFive® Essential™ U54 and U54-MC, 64 bytes
+    pub const U54: Self = Self(64);
+    /// SiFive® Essential™ U74 and U74-MC, 64 bytes
+    pub const U74: Self = Self(64);
+    /// SiFive® Essential™ S76 and S76-MC, 64 bytes
+    pub const S76: Self = Self(64);
+    /// SiFive® Essential™ E76 and E76-MC, 64 bytes
+    pub const E76: Self = Self(64);
+    /// SiFive® Performance™ P550 and P550-MC, 64 bytes
+    pub const P550: Self = Self(64);
+    /// SiFive® Intelligence™ X280 and X280-MC, 64 bytes
+    pub const X280: Self = Self(64);
+
+    /// Cache line size of given bytes
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` is not a power of two.
+    #[inline]
+    pub const fn new(bytes: usize) -> Self {
+        assert!(
+            bytes.is_power_of_two(),
+            "cache line size must be a power of two"
+        );
+        Self(bytes)
+    }
+
+    /// Cache line size in bytes
+    #[inline]
+    pub const fn bytes(self) -> usize {
+        self.0
+    }
+}
+
+// Calls `f` with the aligned address of each cache line covering `start..start + len`
+#[cfg(any(cflush_d_l1_va, cdiscard_d_l1_va))]
+#[inline]
+fn for_each_line(start: usize, len: usize, line: CacheLineSize, mut f: impl FnMut(usize)) {
+    if len == 0 {
+        return;
+    }
+    let line_size = line.bytes();
+    let mut line = start & !(line_size - 1);
+    let last = start.saturating_add(len - 1) & !(line_size - 1);
+    let count = (last - line) / line_size + 1;
+    // unrolled to keep loop overhead small relative to the cache instructions
+    for _ in 0..count / 4 {
+        f(line);
+        f(line + line_size);
+        f(line + 2 * line_size);
+        f(line + 3 * line_size);
+        line = line.wrapping_add(4 * line_size);
+    }
+    for _ in 0..count % 4 {
+        f(line);
+        line = line.wrapping_add(line_size);
+    }
+}
+
+/// Encode a SiFive custom SYSTEM-space instruction for `asm!`
+///
+/// SiFive custom instructions in this crate use the SYSTEM major opcode `0x73` with `funct3` zero,
+/// and are distinguished by the 12-bit immediate field `funct12`. This macro expands to
+/// an assembler directive string for such an instruction, for use as an `asm!` template.
+///
+/// - `sifive_insn!(funct12, rs1, rd)` expands to an `.insn` directive. `rs1` and `rd` are
+///   string literals holding register names or `asm!` operand placeholders like `"{}"`.
+/// - `sifive_insn!(word: funct12, rs1, rd)` expands to a raw `.word` directive for assemblers
+///   without `.insn` support. `rs1` and `rd` are register numbers.
+///
+/// `funct12` is given as unsigned value, e.g. `0xFC0` for CFLUSH.D.L1.
+///
+/// # Example
+///
+/// ```no_run
+/// use core::arch::asm;
+/// use sifive_core::sifive_insn;
+///
+/// // CFLUSH.D.L1 rs1
+/// unsafe { asm!(sifive_insn!(0xFC0, "{}", "x0"), in(reg) va) };
+/// // CEASE
+/// unsafe { asm!(sifive_insn!(word: 0x305, 0, 0), options(noreturn)) };
+/// ```
+#[macro_export]
+macro_rules! sifive_insn {
+    (word: $funct12: literal, $rs1: literal, $rd: literal) => {
+        concat!(
+            ".word ((",
+            $funct12,
+            " & 0xFFF) << 20) | (",
+            $rs1,
+            " << 15) | (",
+            $rd,
+            " << 7) | 0x73"
+        )
+    };
+    ($funct12: literal, $rs1: literal, $rd: literal) => {
+        concat!(
+            ".insn i 0x73, 0, ",
+            $rd,
+            ", ",
+            $rs1,
+            ", ((",
+            $funct12,
+            " & 0xFFF) ^ 0x800) - 0x800"
+        )
+    };
+}
 
 /// CEASE, core halt instruction
 ///
 /// This function will never return and will immediately cease the current hart.
 ///
-/// # Unsafety
+/// # Safety
 ///
 /// Calling this function is unsafe, because the resource this function uses
 /// is not freed (i.e. `fn drop` in Drop trait is not called) after current hart ceased.
@@ -49,7 +199,35 @@ use core::arch::asm;
 #[inline]
 pub unsafe fn cease() -> ! {
     // opcode: 0x30500073
-    asm!(".insn i 0x73, 0, x0, x0, 0x305", options(noreturn))
+    asm!(insn_i!(0x73, 0, 0, 0, 0x305), options(noreturn))
+}
+
+/// CEASE after running a cleanup routine
+///
+/// This function runs `cleanup` on current hart, then executes CEASE. It never returns.
+///
+/// The cleanup routine is the place to free resources that would otherwise leak when the hart
+/// ceases, e.g. draining queues, flushing data caches and fencing outstanding memory accesses.
+///
+/// # Safety
+///
+/// Same as [`cease`], caller must ensure that all resources not freed by `cleanup` are
+/// freed before invoking this function.
+///
+/// # Example
+///
+/// ```no_run
+/// unsafe {
+///     sifive_core::asm::cease_with(|| {
+///         drain_uart_queue();
+///         sifive_core::asm::cflush_d_l1_all();
+///     })
+/// }
+/// ```
+#[inline]
+pub unsafe fn cease_with<F: FnOnce()>(cleanup: F) -> ! {
+    cleanup();
+    cease()
 }
 
 /// CFLUSH.D.L1 x0, L1 data cache full-cache flush instruction
@@ -73,10 +251,12 @@ pub unsafe fn cease() -> ! {
 /// # Hardware implmenetaion
 ///
 /// Implemented as state machine in L1 data cache, for cores with data caches.
+#[cfg(cflush_d_l1_all)]
 #[inline]
 pub fn cflush_d_l1_all() {
+    trace!("flush L1 data cache");
     // opcode: 0xFC000073
-    unsafe { asm!(".insn i 0x73, 0, x0, x0, -0x40") }
+    unsafe { asm!(insn_i!(0x73, 0, 0, 0, -0x40), options(nostack)) }
 }
 
 /// CFLUSH.D.L1 rs1, L1 data cache flush virtual address instruction
@@ -114,10 +294,143 @@ pub fn cflush_d_l1_all() {
 /// # Hardware implmenetaion
 ///
 /// Implemented as state machine in L1 data cache, for cores with data caches.
+#[cfg(cflush_d_l1_va)]
 #[inline]
 pub fn cflush_d_l1_va(va: usize) {
+    trace!("flush L1 data cache line at {:#x}", va);
     // opcode: 0xFC000073 + (rs1 << 15)
-    unsafe { asm!(".insn i 0x73, 0, x0, {}, -0x40", in(reg) va) }
+    unsafe { asm!(insn_i!(0x73, 0, 0, 10, -0x40), in("a0") va, options(nostack)) }
+}
+
+/// Flush all L1 data cache lines covering a virtual address range
+///
+/// This function issues CFLUSH.D.L1 rs1 once for each cache line overlapping the range
+/// from `start` to `start + len`. The start address is rounded down and the end address
+/// rounded up to boundaries of `line` sized cache lines, thus data around the range sharing
+/// cache lines with it is written back as well. Nothing is flushed if `len` is zero.
+///
+/// # Privilege mode permissions
+///
+/// Only available in M-mode.
+///
+/// # Exceptions
+///
+/// Same as [`cflush_d_l1_va`], raised on the first cache line without write permissions.
+///
+/// # Platform support
+///
+/// Same as [`cflush_d_l1_va`], this function requires CFLUSH.D.L1 with rs1 ≠ x0.
+#[cfg(cflush_d_l1_va)]
+#[inline]
+pub fn cflush_d_l1_range(start: usize, len: usize, line: CacheLineSize) {
+    for_each_line(start, len, line, cflush_d_l1_va)
+}
+
+/// Default range length above which [`cflush_d_l1_range_or_all`] flushes the whole L1 data cache
+///
+/// This is half the size of a 32 KiB L1 data cache, as a starting point only. The best value
+/// depends on the core and the amount of dirty data, and should be tuned with measurements
+/// on the target platform.
+#[cfg(all(cflush_d_l1_all, cflush_d_l1_va))]
+pub const FULL_FLUSH_THRESHOLD: usize = 16 * 1024;
+
+/// Flush a virtual address range, or the whole L1 data cache for large ranges
+///
+/// If `len` exceeds `threshold`, this function issues a single CFLUSH.D.L1 x0 to write back and
+/// invalidate the whole L1 data cache, which is faster than flushing each line of a large range.
+/// Otherwise it behaves as [`cflush_d_l1_range`]. [`FULL_FLUSH_THRESHOLD`] is a default value
+/// for `threshold`.
+///
+/// Flushing the whole cache has no effect on memory contents, only other cached data is
+/// written back and evicted as well.
+///
+/// # Privilege mode permissions
+///
+/// Only available in M-mode.
+///
+/// # Platform support
+///
+/// This function requires both CFLUSH.D.L1 x0 and CFLUSH.D.L1 with rs1 ≠ x0,
+/// see [`cflush_d_l1_all`] and [`cflush_d_l1_va`].
+#[cfg(all(cflush_d_l1_all, cflush_d_l1_va))]
+#[inline]
+pub fn cflush_d_l1_range_or_all(start: usize, len: usize, line: CacheLineSize, threshold: usize) {
+    if len > threshold {
+        cflush_d_l1_all()
+    } else {
+        cflush_d_l1_range(start, len, line)
+    }
+}
+
+// Aligned addresses of the cache lines containing `addrs`, skipping an address in the same
+// cache line as the address before it
+#[cfg(any(cflush_d_l1_va, cdiscard_d_l1_va, test))]
+#[inline]
+fn distinct_lines(
+    addrs: impl IntoIterator<Item = usize>,
+    line: CacheLineSize,
+) -> impl Iterator<Item = usize> {
+    let mask = !(line.bytes() - 1);
+    let mut prev = None;
+    addrs
+        .into_iter()
+        .map(move |va| va & mask)
+        .filter(move |&current| prev.replace(current) != Some(current))
+}
+
+/// Flush L1 data cache lines containing scattered virtual addresses
+///
+/// This function issues CFLUSH.D.L1 rs1 for the cache line containing each address yielded
+/// by `addrs`, e.g. a list of dirtied DMA descriptors. Consecutive addresses within the same
+/// `line` sized cache line are flushed only once; only repeats of the previous line are
+/// detected, so an address returning to an earlier line flushes it again. Sort the addresses
+/// to flush each line once. After all lines are flushed, a single
+/// `fence iorw, iorw` orders the write-backs before following memory and I/O accesses,
+/// such as the MMIO write that hands the buffers over to a device.
+///
+/// # Privilege mode permissions
+///
+/// Only available in M-mode.
+///
+/// # Exceptions
+///
+/// Same as [`cflush_d_l1_va`].
+///
+/// # Platform support
+///
+/// Same as [`cflush_d_l1_va`], this function requires CFLUSH.D.L1 with rs1 ≠ x0.
+#[cfg(cflush_d_l1_va)]
+#[inline]
+pub fn cflush_d_l1_iter(addrs: impl IntoIterator<Item = usize>, line: CacheLineSize) {
+    distinct_lines(addrs, line).for_each(cflush_d_l1_va);
+    unsafe { asm!("fence iorw, iorw") }
+}
+
+/// Flush L1 data cache lines holding scattered objects
+///
+/// Writes back and invalidates the cache line containing each pointer yielded by `ptrs`, e.g.
+/// objects updated by a garbage collector or entries of a descriptor ring. The pointed data is
+/// not modified.
+///
+/// This function is a wrapper over [`cflush_d_l1_iter`] and shares its deduplication of
+/// consecutive cache lines, privilege mode, exception and platform support requirements.
+#[cfg(cflush_d_l1_va)]
+#[inline]
+pub fn flush_each(ptrs: impl IntoIterator<Item = *const u8>, line: CacheLineSize) {
+    cflush_d_l1_iter(ptrs.into_iter().map(|ptr| ptr as usize), line)
+}
+
+/// Flush all L1 data cache lines covering a slice
+///
+/// Writes back and invalidates the cache lines holding `data`, e.g. to make a buffer visible
+/// to a DMA device before starting a transfer. The contents of the slice are not modified.
+///
+/// This function is a wrapper over [`cflush_d_l1_range`] and shares its privilege mode,
+/// exception and platform support requirements.
+#[cfg(cflush_d_l1_va)]
+#[inline]
+pub fn flush_slice(data: &[u8], line: CacheLineSize) {
+    cflush_d_l1_range(data.as_ptr() as usize, data.len(), line)
 }
 
 /// CDISCARD.D.L1 x0, L1 data cache full-cache invalidate instruction
@@ -142,10 +455,12 @@ pub fn cflush_d_l1_va(va: usize) {
 /// # Hardware implmenetaion
 ///
 /// Implemented as state machine in L1 data cache, for cores with data caches.
+#[cfg(cdiscard_d_l1_all)]
 #[inline]
 pub fn cdiscard_d_l1_all() {
+    trace!("discard L1 data cache");
     // opcode: 0xFC200073
-    unsafe { asm!(".insn i 0x73, 0, x0, x0, -0x3E") }
+    unsafe { asm!(insn_i!(0x73, 0, 0, 0, -0x3E), options(nostack)) }
 }
 
 /// CDISCARD.D.L1 rs1, L1 data cache invalidate virtual address instruction
@@ -180,10 +495,761 @@ pub fn cdiscard_d_l1_all() {
 /// # Hardware implmenetaion
 ///
 /// Implemented as state machine in L1 data cache, for cores with data caches.
+#[cfg(cdiscard_d_l1_va)]
 #[inline]
 pub fn cdiscard_d_l1_va(va: usize) {
+    trace!("discard L1 data cache line at {:#x}", va);
     // opcode: 0xFC200073 + (rs1 << 15)
-    unsafe { asm!(".insn i 0x73, 0, x0, {}, -0x3E", in(reg) va) }
+    unsafe { asm!(insn_i!(0x73, 0, 0, 10, -0x3E), in("a0") va, options(nostack)) }
+}
+
+/// Invalidate all L1 data cache lines covering a virtual address range
+///
+/// This function issues CDISCARD.D.L1 rs1 once for each cache line overlapping the range
+/// from `start` to `start + len`, without writing back their contents. Nothing is discarded
+/// if `len` is zero.
+///
+/// Typical usage is invalidating a DMA receive buffer before reading data written by a device.
+/// Prefer `discard_slice` where available, which never discards data outside the buffer.
+///
+/// # Safety
+///
+/// Dirty data in the discarded cache lines is lost, whoever it belongs to. Caller must own
+/// the whole range exclusively, and must not rely on its contents afterwards.
+///
+/// The start address is rounded down and the end address rounded up to boundaries of `line`
+/// sized cache lines. If the range does not begin or end on a cache line boundary, dirty data
+/// outside the range sharing the first or last cache line is lost as well. Caller must align
+/// the range to cache lines, or flush the partially covered lines beforehand, e.g. with
+/// `cflush_d_l1_va`.
+///
+/// # Privilege mode permissions
+///
+/// Only available in M-mode.
+///
+/// # Exceptions
+///
+/// Same as [`cdiscard_d_l1_va`], raised on the first cache line without write permissions.
+///
+/// # Platform support
+///
+/// Same as [`cdiscard_d_l1_va`].
+#[cfg(cdiscard_d_l1_va)]
+#[inline]
+pub unsafe fn cdiscard_d_l1_range(start: usize, len: usize, line: CacheLineSize) {
+    for_each_line(start, len, line, cdiscard_d_l1_va)
+}
+
+/// Invalidate L1 data cache lines containing scattered virtual addresses
+///
+/// This function issues CDISCARD.D.L1 rs1 for the cache line containing each address yielded
+/// by `addrs`, without writing back their contents, e.g. receive descriptors written by a
+/// device. As in `cflush_d_l1_iter`, consecutive addresses within the same `line` sized
+/// cache line are discarded only once, and an address returning to an earlier line discards it
+/// again. After all lines are discarded, a single `fence iorw, iorw` orders the invalidations
+/// before following memory and I/O accesses.
+///
+/// # Safety
+///
+/// Dirty data in the discarded cache lines is lost, whoever it belongs to. Caller must own
+/// every whole cache line containing the addresses exclusively, and must not rely on their
+/// contents afterwards.
+///
+/// # Privilege mode permissions
+///
+/// Only available in M-mode.
+///
+/// # Exceptions
+///
+/// Same as [`cdiscard_d_l1_va`].
+///
+/// # Platform support
+///
+/// Same as [`cdiscard_d_l1_va`].
+#[cfg(cdiscard_d_l1_va)]
+#[inline]
+pub unsafe fn cdiscard_d_l1_iter(addrs: impl IntoIterator<Item = usize>, line: CacheLineSize) {
+    distinct_lines(addrs, line).for_each(cdiscard_d_l1_va);
+    asm!("fence iorw, iorw")
+}
+
+/// Invalidate L1 data cache lines holding scattered objects
+///
+/// Invalidates the cache line containing each pointer yielded by `ptrs` without write-back,
+/// so that following reads observe memory contents.
+///
+/// This function is a wrapper over [`cdiscard_d_l1_iter`] and shares its deduplication of
+/// consecutive cache lines, privilege mode, exception and platform support requirements.
+///
+/// # Safety
+///
+/// Same as [`cdiscard_d_l1_iter`]: each whole cache line containing a pointed object is
+/// discarded, including data of other objects sharing the line.
+#[cfg(cdiscard_d_l1_va)]
+#[inline]
+pub unsafe fn discard_each(ptrs: impl IntoIterator<Item = *mut u8>, line: CacheLineSize) {
+    cdiscard_d_l1_iter(ptrs.into_iter().map(|ptr| ptr as usize), line)
+}
+
+/// Invalidate L1 data cache lines covering an exclusively owned buffer
+///
+/// Cache lines lying entirely within `buf` are invalidated without write-back, so that
+/// following reads observe memory contents, e.g. data written by a DMA device. Previous contents
+/// of the buffer are destroyed; taking `&mut [MaybeUninit<u8>]` requires the caller to own the
+/// buffer exclusively and not to assume its contents afterwards.
+///
+/// Cache lines only partially covered by `buf` are shared with memory outside the buffer.
+/// They are flushed instead of discarded, so dirty data belonging to other code is never lost.
+/// Align receive buffers to cache line boundaries to have them discarded entirely.
+///
+/// # Privilege mode permissions
+///
+/// Only available in M-mode.
+///
+/// # Platform support
+///
+/// This function requires both CFLUSH.D.L1 and CDISCARD.D.L1 with rs1 ≠ x0,
+/// see [`cflush_d_l1_va`] and [`cdiscard_d_l1_va`].
+#[cfg(all(cflush_d_l1_va, cdiscard_d_l1_va))]
+#[inline]
+pub fn discard_slice(buf: &mut [MaybeUninit<u8>], line: CacheLineSize) {
+    let line_size = line.bytes();
+    let start = buf.as_mut_ptr() as usize;
+    let end = start + buf.len();
+    let head = (start + line_size - 1) & !(line_size - 1);
+    let tail = end & !(line_size - 1);
+    if head >= tail {
+        // no cache line lies entirely within the buffer
+        cflush_d_l1_range(start, buf.len(), line);
+        return;
+    }
+    if start != head {
+        cflush_d_l1_va(start);
+    }
+    // lines from head to tail lie entirely within the exclusively borrowed buffer
+    unsafe { cdiscard_d_l1_range(head, tail - head, line) };
+    if end != tail {
+        cflush_d_l1_va(tail);
+    }
+}
+
+/// Fence to use before flushing cache lines for a device
+///
+/// Orders all previous memory and I/O accesses of current hart, in particular stores filling
+/// a DMA buffer, before the following cache maintenance instructions and accesses.
+#[inline]
+pub fn fence_before_flush() {
+    unsafe { asm!("fence iorw, iorw", options(nostack)) }
+}
+
+/// Fence to use after invalidating cache lines written by a device
+///
+/// Orders the previous cache maintenance instructions, and the device access that reported
+/// completion of a DMA transfer, before all following memory and I/O accesses of current hart,
+/// so that no load of the buffer is satisfied before the invalidation.
+#[inline]
+pub fn fence_after_invalidate() {
+    unsafe { asm!("fence iorw, iorw", options(nostack)) }
+}
+
+/// FENCE.I, instruction cache flush for current hart
+///
+/// This function synchronizes the instruction and data streams of current hart, so that
+/// instructions stored to memory beforehand are visible to following instruction fetches.
+/// Use it after loading or modifying code, or after writing instructions to an ITIM.
+///
+/// On SiFive cores, FENCE.I flushes the L1 instruction cache and ITIM line buffers of current hart
+/// and waits for prior stores to complete. It does not affect other harts; to make modified code
+/// visible on remote harts, each of them must execute FENCE.I, e.g. on receiving an IPI.
+///
+/// Speculative instruction cache refill is not affected by this function. It is controlled
+/// with the feature disable CSR at boot time and should not be toggled afterwards.
+///
+/// # Platform support
+///
+/// FENCE.I is part of the Zifencei extension, supported by all SiFive cores.
+#[inline]
+pub fn flush_icache() {
+    // opcode: 0x0000100F
+    unsafe { asm!(insn_i!(0x0F, 1, 0, 0, 0)) }
+}
+
+/// Busy-wait for at least `n` core clock cycles
+///
+/// This function spins on PAUSE, which stalls up to 32 cycles on SiFive cores, until the `mcycle`
+/// counter has advanced by `n` since the call. It is intended for hardware bring-up delays where
+/// timers are not configured yet.
+///
+/// # Privilege mode permissions
+///
+/// Only available in M-mode. The `mcycle` counter must not be inhibited in `mcountinhibit`,
+/// or this function never returns.
+///
+/// # Platform support
+///
+/// PAUSE is encoded as a FENCE hint, and executes as a no-op on cores without Zihintpause.
+#[inline]
+pub fn pause_cycles(n: u64) {
+    let start = read_mcycle();
+    while read_mcycle().wrapping_sub(start) < n {
+        // opcode: 0x0100000F
+        unsafe { asm!(insn_i!(0x0F, 0, 0, 0, 0x010)) }
+    }
+}
+
+#[cfg(target_pointer_width = "64")]
+#[inline]
+fn read_mcycle() -> u64 {
+    let ans: u64;
+    unsafe { asm!("csrr {}, mcycle", out(reg) ans) };
+    ans
+}
+
+#[cfg(target_pointer_width = "32")]
+#[inline]
+fn read_mcycle() -> u64 {
+    let (mut hi, mut lo, mut hi2): (u32, u32, u32);
+    loop {
+        unsafe {
+            asm!("csrr {}, mcycleh", "csrr {}, mcycle", "csrr {}, mcycleh",
+                out(reg) hi, out(reg) lo, out(reg) hi2)
+        };
+        if hi == hi2 {
+            return ((hi as u64) << 32) | lo as u64;
+        }
+    }
+}
+
+/// CBO.CLEAN rs1, cache block clean instruction
+///
+/// This instruction writes back the cache block containing the virtual address `va`, if it is
+/// dirty, to all coherent agents and memory. The block remains valid in the caches.
+///
+/// # Privilege mode permissions
+///
+/// Available in M-mode. Availability in S-mode and U-mode is controlled by the `CBCFE` field
+/// of `menvcfg` and `senvcfg`.
+///
+/// # Platform support
+///
+/// This instruction is part of the ratified Zicbom extension, supported by newer SiFive®
+/// Performance™ and Intelligence™ cores. It operates on all cache levels, not only on L1 data cache.
+///
+/// If this instruction is not supported by current platform, an illegal-instruction exception is raised.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub fn cbo_clean(va: usize) {
+    // opcode: 0x0010200F + (rs1 << 15)
+    unsafe { asm!(insn_i!(0x0F, 2, 0, 10, 1), in("a0") va, options(nostack)) }
+}
+
+/// CBO.FLUSH rs1, cache block flush instruction
+///
+/// This instruction writes back the cache block containing the virtual address `va` if it is
+/// dirty, and then invalidates it in all caches.
+///
+/// # Privilege mode permissions
+///
+/// Available in M-mode. Availability in S-mode and U-mode is controlled by the `CBCFE` field
+/// of `menvcfg` and `senvcfg`.
+///
+/// # Platform support
+///
+/// This instruction is part of the ratified Zicbom extension, supported by newer SiFive®
+/// Performance™ and Intelligence™ cores. It operates on all cache levels, not only on L1 data cache.
+///
+/// If this instruction is not supported by current platform, an illegal-instruction exception is raised.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub fn cbo_flush(va: usize) {
+    // opcode: 0x0020200F + (rs1 << 15)
+    unsafe { asm!(insn_i!(0x0F, 2, 0, 10, 2), in("a0") va, options(nostack)) }
+}
+
+/// CBO.INVAL rs1, cache block invalidate instruction
+///
+/// This instruction invalidates, but does not write back, the cache block containing
+/// the virtual address `va` in all caches. Dirty data within the cache block is lost.
+///
+/// # Safety
+///
+/// Dirty data of the whole cache block is discarded, including data outside the intended
+/// buffer sharing the block with it. Caller must own the whole cache block exclusively, and
+/// must not rely on its contents afterwards.
+///
+/// # Privilege mode permissions
+///
+/// Available in M-mode. Availability in S-mode and U-mode is controlled by the `CBIE` field
+/// of `menvcfg` and `senvcfg`; when the field allows it, a lower privilege mode may perform
+/// a flush instead of an invalidation.
+///
+/// # Platform support
+///
+/// This instruction is part of the ratified Zicbom extension, supported by newer SiFive®
+/// Performance™ and Intelligence™ cores. It operates on all cache levels, not only on L1 data cache.
+///
+/// If this instruction is not supported by current platform, an illegal-instruction exception is raised.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub unsafe fn cbo_inval(va: usize) {
+    // opcode: 0x0000200F + (rs1 << 15)
+    asm!(insn_i!(0x0F, 2, 0, 10, 0), in("a0") va, options(nostack))
+}
+
+/// CBO.ZERO rs1, cache block zero instruction
+///
+/// This instruction stores zeros to the full set of bytes of the cache block containing
+/// the virtual address `va`.
+///
+/// # Safety
+///
+/// This instruction writes to memory as a store of the whole cache block would do.
+/// Caller must ensure the whole cache block is valid for writes.
+///
+/// # Privilege mode permissions
+///
+/// Available in M-mode. Availability in S-mode and U-mode is controlled by the `CBZE` field
+/// of `menvcfg` and `senvcfg`.
+///
+/// # Platform support
+///
+/// This instruction is part of the ratified Zicboz extension, supported by newer SiFive®
+/// Performance™ and Intelligence™ cores.
+///
+/// If this instruction is not supported by current platform, an illegal-instruction exception is raised.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub unsafe fn cbo_zero(va: usize) {
+    // opcode: 0x0040200F + (rs1 << 15)
+    asm!(insn_i!(0x0F, 2, 0, 10, 4), in("a0") va, options(nostack))
+}
+
+/// Zero a virtual address range using cache block zero instructions
+///
+/// Cache blocks lying entirely within the range from `start` to `start + len` are zeroed
+/// with CBO.ZERO; unaligned head and tail bytes sharing cache blocks with memory outside
+/// the range are zeroed using ordinary stores.
+///
+/// # Safety
+///
+/// Caller must ensure the range is valid for writes.
+///
+/// # Platform support
+///
+/// Same as [`cbo_zero`]. The `block` parameter must match the Zicboz cache block size
+/// of current platform.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub unsafe fn zero_range(start: usize, len: usize, block: CacheLineSize) {
+    let block_size = block.bytes();
+    let end = start + len;
+    let head = (start + block_size - 1) & !(block_size - 1);
+    let tail = end & !(block_size - 1);
+    if head >= tail {
+        // no cache block lies entirely within the range
+        core::ptr::write_bytes(start as *mut u8, 0, len);
+        return;
+    }
+    core::ptr::write_bytes(start as *mut u8, 0, head - start);
+    let mut va = head;
+    while va < tail {
+        cbo_zero(va);
+        va += block_size;
+    }
+    core::ptr::write_bytes(tail as *mut u8, 0, end - tail);
+}
+
+/// PREFETCH.R offset(rs1), prefetch for data read hint
+///
+/// This instruction indicates to hardware that the cache block containing the virtual address `va`
+/// is likely to be accessed by a data read in the near future.
+///
+/// # Platform support
+///
+/// This instruction is part of the ratified Zicbop extension. It is encoded as a hint
+/// (`ORI x0, rs1, 1`), thus it executes as a no-op on platforms without Zicbop and never raises
+/// an illegal-instruction exception.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub fn prefetch_r(va: usize) {
+    // opcode: 0x00106013 + (rs1 << 15)
+    unsafe { asm!(insn_i!(0x13, 6, 0, 10, 1), in("a0") va) }
+}
+
+/// PREFETCH.W offset(rs1), prefetch for data write hint
+///
+/// This instruction indicates to hardware that the cache block containing the virtual address `va`
+/// is likely to be accessed by a data write in the near future.
+///
+/// # Platform support
+///
+/// This instruction is part of the ratified Zicbop extension. It is encoded as a hint
+/// (`ORI x0, rs1, 3`), thus it executes as a no-op on platforms without Zicbop and never raises
+/// an illegal-instruction exception.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub fn prefetch_w(va: usize) {
+    // opcode: 0x00306013 + (rs1 << 15)
+    unsafe { asm!(insn_i!(0x13, 6, 0, 10, 3), in("a0") va) }
+}
+
+/// PREFETCH.I offset(rs1), prefetch for instruction fetch hint
+///
+/// This instruction indicates to hardware that the cache block containing the virtual address `va`
+/// is likely to be accessed by an instruction fetch in the near future.
+///
+/// # Platform support
+///
+/// This instruction is part of the ratified Zicbop extension. It is encoded as a hint
+/// (`ORI x0, rs1, 0`), thus it executes as a no-op on platforms without Zicbop and never raises
+/// an illegal-instruction exception.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub fn prefetch_i(va: usize) {
+    // opcode: 0x00006013 + (rs1 << 15)
+    unsafe { asm!(insn_i!(0x13, 6, 0, 10, 0), in("a0") va) }
+}
+
+/// NTL.P1, non-temporal locality hint for the innermost private cache
+///
+/// This hint indicates that the memory access immediately following it does not exhibit
+/// temporal locality within the innermost level of private cache.
+///
+/// Non-temporal locality hints apply only to the next instruction. As the compiler is free to
+/// schedule other instructions in between, this function alone does not reliably hint a
+/// particular access; use [`with_ntl`] to issue hinted loads and stores.
+///
+/// # Platform support
+///
+/// This instruction is part of the ratified Zihintntl extension, implemented on SiFive
+/// Intelligence X280 cores. It is encoded as a hint (`ADD x0, x0, x2`), thus it executes as
+/// a no-op on platforms without Zihintntl.
+#[inline]
+pub fn ntl_p1() {
+    unsafe { asm!("add x0, x0, x2", options(nomem, nostack)) }
+}
+
+/// NTL.PALL, non-temporal locality hint for all private caches
+///
+/// This hint indicates that the memory access immediately following it does not exhibit
+/// temporal locality within any level of private cache. See [`ntl_p1`] for scheduling notes.
+///
+/// # Platform support
+///
+/// Encoded as a hint (`ADD x0, x0, x3`) of the Zihintntl extension.
+#[inline]
+pub fn ntl_pall() {
+    unsafe { asm!("add x0, x0, x3", options(nomem, nostack)) }
+}
+
+/// NTL.S1, non-temporal locality hint for the innermost shared cache
+///
+/// This hint indicates that the memory access immediately following it does not exhibit
+/// temporal locality within the private caches and the innermost level of shared cache.
+/// See [`ntl_p1`] for scheduling notes.
+///
+/// # Platform support
+///
+/// Encoded as a hint (`ADD x0, x0, x4`) of the Zihintntl extension.
+#[inline]
+pub fn ntl_s1() {
+    unsafe { asm!("add x0, x0, x4", options(nomem, nostack)) }
+}
+
+/// NTL.ALL, non-temporal locality hint for all cache levels
+///
+/// This hint indicates that the memory access immediately following it does not exhibit
+/// temporal locality within any level of cache. See [`ntl_p1`] for scheduling notes.
+///
+/// # Platform support
+///
+/// Encoded as a hint (`ADD x0, x0, x5`) of the Zihintntl extension.
+#[inline]
+pub fn ntl_all() {
+    unsafe { asm!("add x0, x0, x5", options(nomem, nostack)) }
+}
+
+/// Cache level beyond which data is not expected to be reused
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Locality {
+    /// Innermost private cache, as hinted by [`ntl_p1`]
+    P1,
+    /// All private caches, as hinted by [`ntl_pall`]
+    Pall,
+    /// Innermost shared cache, as hinted by [`ntl_s1`]
+    S1,
+    /// All caches, as hinted by [`ntl_all`]
+    All,
+}
+
+/// Word sized memory accesses each preceded by a non-temporal locality hint
+///
+/// Each access is emitted in the same assembly block as its hint, so that the hint
+/// immediately precedes the load or store it applies to.
+#[derive(Clone, Copy, Debug)]
+pub struct NtlAccess {
+    locality: Locality,
+}
+
+macro_rules! ntl_asm {
+    ($locality: expr, $insn: expr, $($args: tt)*) => {
+        match $locality {
+            Locality::P1 => asm!(concat!("add x0, x0, x2\n", $insn), $($args)*),
+            Locality::Pall => asm!(concat!("add x0, x0, x3\n", $insn), $($args)*),
+            Locality::S1 => asm!(concat!("add x0, x0, x4\n", $insn), $($args)*),
+            Locality::All => asm!(concat!("add x0, x0, x5\n", $insn), $($args)*),
+        }
+    };
+}
+
+impl NtlAccess {
+    /// Locality hinted on each access
+    #[inline]
+    pub fn locality(&self) -> Locality {
+        self.locality
+    }
+    /// Reads a word from `src` with a non-temporal locality hint
+    ///
+    /// # Safety
+    ///
+    /// `src` must be valid for reads and aligned to `usize`.
+    #[inline]
+    pub unsafe fn read(&self, src: *const usize) -> usize {
+        let ans: usize;
+        ntl_asm!(self.locality, load_word!("{0}", "{1}"), out(reg) ans, in(reg) src, options(readonly, nostack));
+        ans
+    }
+    /// Writes a word to `dst` with a non-temporal locality hint
+    ///
+    /// # Safety
+    ///
+    /// `dst` must be valid for writes and aligned to `usize`.
+    #[inline]
+    pub unsafe fn write(&self, dst: *mut usize, value: usize) {
+        ntl_asm!(self.locality, store_word!("{0}", "{1}"), in(reg) value, in(reg) dst, options(nostack));
+    }
+}
+
+/// Runs `f` with an accessor issuing memory accesses hinted with the given locality
+///
+/// Streaming code touching one-shot data may route its loads and stores through the
+/// [`NtlAccess`] to avoid evicting reused data from caches. Accesses made by `f` in other
+/// ways are not hinted.
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::asm::{with_ntl, Locality};
+/// # let (src, dst, len): (*const usize, *mut usize, usize) = (core::ptr::null(), core::ptr::null_mut(), 0);
+/// with_ntl(Locality::All, |ntl| unsafe {
+///     for i in 0..len {
+///         ntl.write(dst.add(i), ntl.read(src.add(i)));
+///     }
+/// });
+/// ```
+#[inline]
+pub fn with_ntl<R>(locality: Locality, f: impl FnOnce(NtlAccess) -> R) -> R {
+    f(NtlAccess { locality })
+}
+
+/// Error returned by fallible instruction wrappers on harts without the instruction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NotSupported;
+
+/// CFLUSH.D.L1 x0 if supported, see [`cflush_d_l1_all`]
+///
+/// Returns `Err(NotSupported)` without executing the instruction if `support` does not contain
+/// [`Support::CFLUSH_D_L1_ALL`].
+#[cfg(cflush_d_l1_all)]
+#[inline]
+pub fn try_cflush_d_l1_all(support: Support) -> Result<(), NotSupported> {
+    if !support.contains(Support::CFLUSH_D_L1_ALL) {
+        return Err(NotSupported);
+    }
+    cflush_d_l1_all();
+    Ok(())
+}
+
+/// CFLUSH.D.L1 rs1 if supported, see [`cflush_d_l1_va`]
+///
+/// Returns `Err(NotSupported)` without executing the instruction if `support` does not contain
+/// [`Support::CFLUSH_D_L1_VA`].
+#[cfg(cflush_d_l1_va)]
+#[inline]
+pub fn try_cflush_d_l1_va(va: usize, support: Support) -> Result<(), NotSupported> {
+    if !support.contains(Support::CFLUSH_D_L1_VA) {
+        return Err(NotSupported);
+    }
+    cflush_d_l1_va(va);
+    Ok(())
+}
+
+/// CDISCARD.D.L1 x0 if supported, see [`cdiscard_d_l1_all`]
+///
+/// Returns `Err(NotSupported)` without executing the instruction if `support` does not contain
+/// [`Support::CDISCARD_D_L1_ALL`].
+#[cfg(cdiscard_d_l1_all)]
+#[inline]
+pub fn try_cdiscard_d_l1_all(support: Support) -> Result<(), NotSupported> {
+    if !support.contains(Support::CDISCARD_D_L1_ALL) {
+        return Err(NotSupported);
+    }
+    cdiscard_d_l1_all();
+    Ok(())
+}
+
+/// CDISCARD.D.L1 rs1 if supported, see [`cdiscard_d_l1_va`]
+///
+/// Returns `Err(NotSupported)` without executing the instruction if `support` does not contain
+/// [`Support::CDISCARD_D_L1_VA`].
+#[cfg(cdiscard_d_l1_va)]
+#[inline]
+pub fn try_cdiscard_d_l1_va(va: usize, support: Support) -> Result<(), NotSupported> {
+    if !support.contains(Support::CDISCARD_D_L1_VA) {
+        return Err(NotSupported);
+    }
+    cdiscard_d_l1_va(va);
+    Ok(())
+}
+
+/// CBO.CLEAN if supported, see [`cbo_clean`]
+///
+/// Returns `Err(NotSupported)` without executing the instruction if `support` does not contain
+/// [`Support::CBO_CLEAN`].
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub fn try_cbo_clean(va: usize, support: Support) -> Result<(), NotSupported> {
+    if !support.contains(Support::CBO_CLEAN) {
+        return Err(NotSupported);
+    }
+    cbo_clean(va);
+    Ok(())
+}
+
+/// CBO.FLUSH if supported, see [`cbo_flush`]
+///
+/// Returns `Err(NotSupported)` without executing the instruction if `support` does not contain
+/// [`Support::CBO_FLUSH`].
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub fn try_cbo_flush(va: usize, support: Support) -> Result<(), NotSupported> {
+    if !support.contains(Support::CBO_FLUSH) {
+        return Err(NotSupported);
+    }
+    cbo_flush(va);
+    Ok(())
+}
+
+/// CBO.INVAL if supported, see [`cbo_inval`]
+///
+/// Returns `Err(NotSupported)` without executing the instruction if `support` does not contain
+/// [`Support::CBO_INVAL`].
+///
+/// # Safety
+///
+/// Same as [`cbo_inval`].
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub unsafe fn try_cbo_inval(va: usize, support: Support) -> Result<(), NotSupported> {
+    if !support.contains(Support::CBO_INVAL) {
+        return Err(NotSupported);
+    }
+    cbo_inval(va);
+    Ok(())
+}
+
+/// CBO.ZERO if supported, see [`cbo_zero`]
+///
+/// Returns `Err(NotSupported)` without executing the instruction if `support` does not contain
+/// [`Support::CBO_ZERO`].
+///
+/// # Safety
+///
+/// Same as [`cbo_zero`].
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub unsafe fn try_cbo_zero(va: usize, support: Support) -> Result<(), NotSupported> {
+    if !support.contains(Support::CBO_ZERO) {
+        return Err(NotSupported);
+    }
+    cbo_zero(va);
+    Ok(())
+}
+
+/// SiFive Vector Coprocessor Interface eXtension (VCIX) instructions
+///
+/// VCIX instructions `sf.vc.*` send scalar and vector operands to a custom vector coprocessor
+/// attached to SiFive® Intelligence™ X280 cores, and optionally write a vector result back.
+///
+/// The opcode fields of these instructions are immediates, and most forms take vector register
+/// operands, which cannot be passed as values to inline assembly. Thus this module provides macros
+/// building assembly templates rather than functions:
+///
+/// - [`vcix!`](crate::vcix) wraps any `sf.vc.*` instruction lines into an `asm!` template
+///   that enables the `xsfvcp` extension in the assembler;
+/// - [`sf_vc_x!`](crate::sf_vc_x) and [`sf_vc_i!`](crate::sf_vc_i) execute the scalar forms
+///   without vector operands directly.
+///
+/// # Example
+///
+/// ```no_run
+/// use core::arch::asm;
+/// use sifive_core::vcix;
+///
+/// // send v8 and v9 to the coprocessor, opcode 3, funct field 31
+/// unsafe { asm!(vcix!("sf.vc.vv 3, 31, v8, v9")) };
+/// // send a scalar operand, write the result to v8
+/// unsafe { asm!(vcix!("sf.vc.v.x 3, 31, v8, {0}"), in(reg) value) };
+/// ```
+///
+/// # Platform support
+///
+/// VCIX is supported by SiFive® Intelligence™ X280 cores with a coprocessor attached.
+/// Vector instructions must be enabled in `mstatus.VS` and a vector configuration
+/// must be set with `vsetvli` before executing VCIX instructions.
+///
+/// If these instructions are not supported by current platform, an illegal-instruction exception is raised.
+pub mod vcix {
+    /// Wrap `sf.vc.*` instruction lines into an `asm!` template
+    ///
+    /// See [module documentation](crate::asm::vcix) for details.
+    #[macro_export]
+    macro_rules! vcix {
+        ($($insn: expr),+ $(,)?) => {
+            concat!(".option push\n.option arch, +xsfvcp\n", $($insn, "\n",)+ ".option pop")
+        };
+    }
+
+    /// SF.VC.X, send a scalar register operand to the coprocessor
+    ///
+    /// `sf_vc_x!(opcode, rs2, rd, value)` sends `value` with 2-bit `opcode` and 5-bit
+    /// immediate fields `rs2` and `rd`, without vector operands or result.
+    /// Must be used within an `unsafe` block.
+    #[macro_export]
+    macro_rules! sf_vc_x {
+        ($opcode: literal, $rs2: literal, $rd: literal, $value: expr) => {
+            core::arch::asm!(
+                $crate::vcix!(concat!("sf.vc.x ", $opcode, ", ", $rs2, ", ", $rd, ", {0}")),
+                in(reg) $value
+            )
+        };
+    }
+
+    /// SF.VC.I, send a 5-bit signed immediate to the coprocessor
+    ///
+    /// `sf_vc_i!(opcode, rs2, rd, imm)` sends immediate `imm` with 2-bit `opcode` and 5-bit
+    /// immediate fields `rs2` and `rd`, without vector operands or result.
+    /// Must be used within an `unsafe` block.
+    #[macro_export]
+    macro_rules! sf_vc_i {
+        ($opcode: literal, $rs2: literal, $rd: literal, $imm: literal) => {
+            core::arch::asm!($crate::vcix!(concat!(
+                "sf.vc.i ", $opcode, ", ", $rs2, ", ", $rd, ", ", $imm
+            )))
+        };
+    }
 }
 
 /// MNRET, non-maskable interrupt return instruction
@@ -191,8 +1257,107 @@ pub fn cdiscard_d_l1_va(va: usize) {
 /// This M-mode only instruction uses the values in `mnepc` and `mnstatus` to return
 /// to the program counter and privileged mode of the interrupted context, respectively.
 /// This instruction also sets the internal `rnmie` state bits.
+///
+/// # Safety
+///
+/// Must run on M mode in an RNMI handler, with `mnepc` and `mnstatus` describing the context to
+/// resume.
 #[inline]
 pub unsafe fn mnret() -> ! {
     // opcode: 0x70200073
-    asm!(".insn i 0x73, 0, x0, x0, 0x702", options(noreturn))
+    asm!(insn_i!(0x73, 0, 0, 0, 0x702), options(noreturn))
+}
+
+/// Restore an interrupted context and return from the RNMI handler
+///
+/// This function writes `mnepc` and `mnstatus` from the frame, restores all general purpose
+/// registers from the frame and executes MNRET. The `mncause` field is ignored.
+///
+/// # Safety
+///
+/// The frame must describe a valid context to resume, usually one saved on entry to
+/// the RNMI handler and possibly modified by it. Resources on current stack are not freed.
+///
+/// # Privilege mode permissions
+///
+/// Only available in M-mode, from within an RNMI handler.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[inline]
+pub unsafe fn nmi_return(frame: &NmiFrame) -> ! {
+    asm!(
+        load_frame!("t0", "32", "a0"),
+        concat!("csrw ", __rnmi_csr!(mnepc), ", t0"),
+        load_frame!("t0", "34", "a0"),
+        concat!("csrw ", __rnmi_csr!(mnstatus), ", t0"),
+        load_frame!("x1", "1", "a0"),
+        load_frame!("x2", "2", "a0"),
+        load_frame!("x3", "3", "a0"),
+        load_frame!("x4", "4", "a0"),
+        load_frame!("x5", "5", "a0"),
+        load_frame!("x6", "6", "a0"),
+        load_frame!("x7", "7", "a0"),
+        load_frame!("x8", "8", "a0"),
+        load_frame!("x9", "9", "a0"),
+        load_frame!("x11", "11", "a0"),
+        load_frame!("x12", "12", "a0"),
+        load_frame!("x13", "13", "a0"),
+        load_frame!("x14", "14", "a0"),
+        load_frame!("x15", "15", "a0"),
+        load_frame!("x16", "16", "a0"),
+        load_frame!("x17", "17", "a0"),
+        load_frame!("x18", "18", "a0"),
+        load_frame!("x19", "19", "a0"),
+        load_frame!("x20", "20", "a0"),
+        load_frame!("x21", "21", "a0"),
+        load_frame!("x22", "22", "a0"),
+        load_frame!("x23", "23", "a0"),
+        load_frame!("x24", "24", "a0"),
+        load_frame!("x25", "25", "a0"),
+        load_frame!("x26", "26", "a0"),
+        load_frame!("x27", "27", "a0"),
+        load_frame!("x28", "28", "a0"),
+        load_frame!("x29", "29", "a0"),
+        load_frame!("x30", "30", "a0"),
+        load_frame!("x31", "31", "a0"),
+        load_frame!("x10", "10", "a0"),
+        insn_i!(0x73, 0, 0, 0, 0x702),
+        in("a0") frame,
+        options(noreturn)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{distinct_lines, CacheLineSize};
+
+    fn lines<const N: usize>(addrs: [usize; N]) -> ([usize; N], usize) {
+        let mut ans = [0; N];
+        let mut len = 0;
+        for line in distinct_lines(addrs, CacheLineSize::new(64)) {
+            ans[len] = line;
+            len += 1;
+        }
+        (ans, len)
+    }
+
+    #[test]
+    fn same_line_once() {
+        assert_eq!(lines([0x1000, 0x1008, 0x103F]), ([0x1000, 0, 0], 1));
+    }
+
+    #[test]
+    fn different_lines_each() {
+        assert_eq!(
+            lines([0x1000, 0x1040, 0x1048, 0x20C0]),
+            ([0x1000, 0x1040, 0x20C0, 0], 3)
+        );
+    }
+
+    #[test]
+    fn only_consecutive_lines_deduplicated() {
+        assert_eq!(
+            lines([0x1000, 0x1040, 0x1010]),
+            ([0x1000, 0x1040, 0x1000], 3)
+        );
+    }
 }
@@ -0,0 +1,202 @@
+//! Raw register dump for remote diagnostics
+//!
+//! A [`CoreSnapshot`] holds the identification, feature disable, bpm and RNMI registers of a
+//! hart. [`encode`] serializes the snapshot of current hart into a compact fixed layout that
+//! can be sent over a raw UART or similar link without `log` or `defmt`, and host tooling
+//! parses the bytes back with [`CoreSnapshot::decode`].
+use crate::register::{identity, mbpm, mfeature, mncause, mnepc};
+use core::arch::asm;
+
+/// Registers of a hart captured for diagnostics
+///
+/// The wire layout starts with a version byte, followed by all fields as little-endian 64-bit
+/// values, so that it does not depend on XLEN:
+///
+/// | Offset | Field |
+/// |:-------|:------|
+/// | 0 | version, 8 bits |
+/// | 1 | `mhartid` |
+/// | 9 | `mvendorid` |
+/// | 17 | `marchid` |
+/// | 25 | `mimpid` |
+/// | 33 | feature disable register |
+/// | 41 | bpm register |
+/// | 49 | `mncause` |
+/// | 57 | `mnepc` |
+///
+/// Later versions only append fields. Decoders accept snapshots of newer versions and ignore
+/// the fields they do not know.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CoreSnapshot {
+    /// Hart ID
+    pub mhartid: u64,
+    /// Vendor ID of the core
+    pub mvendorid: u64,
+    /// Architecture ID of the core
+    pub marchid: u64,
+    /// Implementation ID of the core
+    pub mimpid: u64,
+    /// Value of the feature disable register
+    pub features_disabled: u64,
+    /// Value of the bpm register
+    pub bpm: u64,
+    /// Cause of the last RNMI
+    pub mncause: u64,
+    /// Interrupted program counter of the last RNMI
+    pub mnepc: u64,
+}
+
+/// Error decoding a [`CoreSnapshot`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// The version byte predates the first version of the layout
+    UnsupportedVersion,
+    /// The buffer is shorter than the snapshot
+    Truncated,
+}
+
+impl CoreSnapshot {
+    /// Version of the layout written by this crate
+    pub const VERSION: u8 = 1;
+    /// Size of an encoded snapshot of [`CoreSnapshot::VERSION`] in bytes
+    pub const ENCODED_SIZE: usize = 65;
+
+    /// Reads the registers of current hart
+    ///
+    /// Must run on M mode, on cores implementing the RNMI registers.
+    #[inline]
+    pub fn read() -> Self {
+        let mhartid: usize;
+        unsafe { asm!("csrr {}, mhartid", out(reg) mhartid) };
+        let identity = identity::read();
+        Self {
+            mhartid: mhartid as u64,
+            mvendorid: identity.mvendorid as u64,
+            marchid: identity.marchid as u64,
+            mimpid: identity.mimpid as u64,
+            features_disabled: mfeature::read().mask().bits() as u64,
+            bpm: mbpm::read().bits() as u64,
+            mncause: mncause::read().bits() as u64,
+            mnepc: mnepc::read() as u64,
+        }
+    }
+
+    /// Encodes the snapshot into `buf`, returning the number of bytes written
+    ///
+    /// Returns None if `buf` is shorter than [`CoreSnapshot::ENCODED_SIZE`].
+    pub fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        let buf = buf.get_mut(..Self::ENCODED_SIZE)?;
+        buf[0] = Self::VERSION;
+        let fields = [
+            self.mhartid,
+            self.mvendorid,
+            self.marchid,
+            self.mimpid,
+            self.features_disabled,
+            self.bpm,
+            self.mncause,
+            self.mnepc,
+        ];
+        for (chunk, field) in buf[1..].chunks_exact_mut(8).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        Some(Self::ENCODED_SIZE)
+    }
+
+    /// Decodes a snapshot from `buf`, returning the snapshot and its version
+    pub fn decode(buf: &[u8]) -> Result<(Self, u8), DecodeError> {
+        let version = *buf.first().ok_or(DecodeError::Truncated)?;
+        if version == 0 {
+            return Err(DecodeError::UnsupportedVersion);
+        }
+        if buf.len() < Self::ENCODED_SIZE {
+            return Err(DecodeError::Truncated);
+        }
+        let field = |index: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&buf[1 + index * 8..9 + index * 8]);
+            u64::from_le_bytes(bytes)
+        };
+        let snapshot = Self {
+            mhartid: field(0),
+            mvendorid: field(1),
+            marchid: field(2),
+            mimpid: field(3),
+            features_disabled: field(4),
+            bpm: field(5),
+            mncause: field(6),
+            mnepc: field(7),
+        };
+        Ok((snapshot, version))
+    }
+}
+
+/// Encodes the registers of current hart into `buf`, returning the number of bytes written
+///
+/// Returns None if `buf` is shorter than [`CoreSnapshot::ENCODED_SIZE`]. Must run on M mode,
+/// on cores implementing the RNMI registers.
+///
+/// # Example
+///
+/// ```no_run
+/// let mut buf = [0; sifive_core::dump::CoreSnapshot::ENCODED_SIZE];
+/// if let Some(len) = sifive_core::dump::encode(&mut buf) {
+///     uart.write_all(&buf[..len]);
+/// }
+/// ```
+#[inline]
+pub fn encode(buf: &mut [u8]) -> Option<usize> {
+    CoreSnapshot::read().encode(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CoreSnapshot, DecodeError};
+
+    const SNAPSHOT: CoreSnapshot = CoreSnapshot {
+        mhartid: 1,
+        mvendorid: 0x489,
+        marchid: 0x8000_0000_0000_0007,
+        mimpid: 0x0421_0427,
+        features_disabled: 0x200,
+        bpm: 1,
+        mncause: 0x8000_0000_0000_0003,
+        mnepc: 0x8020_0000,
+    };
+
+    fn encoded() -> [u8; CoreSnapshot::ENCODED_SIZE] {
+        let mut buf = [0; CoreSnapshot::ENCODED_SIZE];
+        assert_eq!(SNAPSHOT.encode(&mut buf), Some(CoreSnapshot::ENCODED_SIZE));
+        buf
+    }
+
+    #[test]
+    fn snapshot_round_trip() {
+        assert_eq!(
+            CoreSnapshot::decode(&encoded()),
+            Ok((SNAPSHOT, CoreSnapshot::VERSION))
+        );
+        assert_eq!(
+            SNAPSHOT.encode(&mut [0; CoreSnapshot::ENCODED_SIZE - 1]),
+            None
+        );
+    }
+
+    #[test]
+    fn snapshot_rejects_bad_input() {
+        let mut buf = encoded();
+        buf[0] = 0;
+        assert_eq!(
+            CoreSnapshot::decode(&buf),
+            Err(DecodeError::UnsupportedVersion)
+        );
+        let buf = encoded();
+        assert_eq!(
+            CoreSnapshot::decode(&buf[..CoreSnapshot::ENCODED_SIZE - 1]),
+            Err(DecodeError::Truncated)
+        );
+        assert_eq!(CoreSnapshot::decode(&[]), Err(DecodeError::Truncated));
+    }
+}
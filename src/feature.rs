@@ -1,8 +1,36 @@
 //! SiFive platform features
-use crate::register::mfeature;
+//!
+//! Features are masked by the per-hart feature disable register, which resets with all
+//! implemented bits set. The API of this module is organized as:
+//!
+//! - [`Mask`] names the documented bits of the register;
+//! - [`FeatureBuilder`], [`enable`] and [`enable_all`] clear bits to enable features;
+//! - [`status`] reads the register back for diagnostics;
+//! - [`validate`] and [`apply_workarounds`] check a mask against the core model and errata;
+//! - [`boot_init`] runs the whole bring-up sequence on current hart.
+//!
+//! Raw register access is provided by [`crate::register::mfeature`].
+use crate::asm::CacheLineSize;
+use crate::register::identity::{self, CoreModel, Identity};
+use crate::register::{mbpm, mbpm::Mbpm, mfeature};
+use core::fmt;
 
 bitflags::bitflags! {
     /// Mask SiFive platform features
+    ///
+    /// The flags follow the feature disable register of SiFive 7-series cores. Other cores
+    /// implement a subset of them; see [`Mask::supported`] for the bits documented per core.
+    ///
+    /// Per-core constants such as `U74::SUPPORTED` are not provided. E76, S76 and U74 document
+    /// the same bits, which are all the named flags, so such constants would all equal
+    /// [`Mask::all`]. The core model is usually only known at runtime from
+    /// [`identity`](crate::register::identity), and for cores whose bits are not tabulated, such
+    /// as U54, a constant could not tell "unknown" apart from "nothing implemented";
+    /// [`Mask::supported`] takes the detected model and returns None for those cores.
+    ///
+    /// Manuals up to the U74-MC and S76-MC do not document further bits: bits 4 to 8, 10 to 15
+    /// and 18 upwards are reserved. Such bits are kept as unknown bits rather than named here.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub struct Mask: usize {
         /// Disable data cache clock gating
         const DCACHE_CLOCK_GATING = 1 << 0;
@@ -23,8 +51,684 @@ bitflags::bitflags! {
 
 /// Enable features on bootloading
 ///
+/// # Safety
+///
 /// Must run on M mode.
 #[inline]
 pub unsafe fn enable(flags: Mask) {
+    debug!("enable features {:?}", flags);
     mfeature::clear_features(flags)
 }
+
+/// Enable all features on bootloading
+///
+/// Writes zero to the feature disable register, turning on the maximal set of features
+/// as SiFive's Freedom Metal bootloader does.
+///
+/// # Safety
+///
+/// Must run on M mode.
+#[inline]
+pub unsafe fn enable_all() {
+    debug!("enable all features");
+    mfeature::clear_all()
+}
+
+/// Clear raw bits in the feature disable register
+///
+/// Escape hatch for chicken bits provided by SiFive support that are not in public manuals.
+/// Unlike [`enable`], no bit is checked against the documented set.
+///
+/// # Safety
+///
+/// Must run on M mode. Clearing undocumented bits changes core behavior in ways this crate
+/// cannot describe; only clear bits as instructed by the silicon vendor.
+#[inline]
+pub unsafe fn clear_raw(bits: usize) {
+    debug!("clear raw feature bits {:#x}", bits);
+    mfeature::clear_features(Mask::from_raw(bits))
+}
+
+impl Mask {
+    /// Builds a mask from raw register bits, keeping bits not documented by this crate
+    ///
+    /// Intended for chicken bits provided by SiFive support that are not in public manuals.
+    /// Prefer the named flags for documented features.
+    #[inline]
+    pub const fn from_raw(bits: usize) -> Mask {
+        Mask::from_bits_retain(bits)
+    }
+
+    /// Feature disable bits documented for the given core model
+    ///
+    /// All named flags follow the 7-series register, so E76, S76 and U74 cores document every
+    /// bit of [`Mask::all`]. Returns None for cores whose feature disable bits are not
+    /// tabulated by this crate.
+    #[inline]
+    pub const fn supported(core: CoreModel) -> Option<Mask> {
+        match core {
+            CoreModel::E76 | CoreModel::S76 | CoreModel::U74 => Some(Mask::all()),
+            CoreModel::U54 => None,
+        }
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Mask {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "Mask({=usize:#x})", self.bits())
+    }
+}
+
+/// Feature disable bits not documented for a core, returned by [`validate`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UnsupportedBits {
+    /// Bits of the validated mask outside the documented set of the core
+    pub bits: Mask,
+}
+
+/// Check that all features in `flags` are documented for the core model
+///
+/// Clearing a bit the core hardwires to zero has no effect, which hides configuration
+/// mistakes of bootloaders built for several cores. This function returns the offending bits
+/// instead. For core models whose feature disable bits are not tabulated by this crate, see
+/// [`Mask::supported`], every bit is reported.
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::{feature, register::identity};
+///
+/// let flags = feature::Mask::ICACHE_NEXT_LINE_PREFETCH;
+/// if let Some(core) = identity::read().core_model() {
+///     feature::validate(flags, core).expect("feature not available on this core");
+/// }
+/// ```
+#[inline]
+pub fn validate(flags: Mask, core: CoreModel) -> Result<(), UnsupportedBits> {
+    let supported = Mask::supported(core).unwrap_or_else(Mask::empty);
+    let bits = flags.difference(supported);
+    if bits.is_empty() {
+        Ok(())
+    } else {
+        Err(UnsupportedBits { bits })
+    }
+}
+
+/// Feature disable state of current hart, returned by [`status`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FeatureStatus {
+    /// Core model of current hart, if detected
+    pub core: Option<CoreModel>,
+    /// Features still disabled, including bits unknown to [`Mask`]
+    pub disabled: Mask,
+    /// Documented features of the core that are enabled
+    pub enabled: Mask,
+    /// Features not documented for the core, hardwired to zero
+    pub not_implemented: Mask,
+    /// Features reading zero on cores whose documented bits are not tabulated by this crate,
+    /// which are either enabled or not implemented
+    pub undetermined: Mask,
+}
+
+impl fmt::Display for FeatureStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "enabled: {:?}, disabled: {:?}, not implemented: {:?}",
+            self.enabled, self.disabled, self.not_implemented
+        )?;
+        if !self.undetermined.is_empty() {
+            write!(f, ", undetermined: {:?}", self.undetermined)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reports which features of current hart are enabled, disabled or not implemented
+///
+/// Combines the feature disable register with the bits documented for the core model
+/// detected by [`identity`], for boot logs and crash dumps.
+#[inline]
+pub fn status() -> FeatureStatus {
+    let disabled = mfeature::read().mask();
+    let core = identity::read().core_model();
+    let cleared = Mask::all().difference(disabled);
+    match core.and_then(Mask::supported) {
+        Some(supported) => FeatureStatus {
+            core,
+            disabled,
+            enabled: cleared.intersection(supported),
+            not_implemented: cleared.difference(supported),
+            undetermined: Mask::empty(),
+        },
+        None => FeatureStatus {
+            core,
+            disabled,
+            enabled: Mask::empty(),
+            not_implemented: Mask::empty(),
+            undetermined: cleared,
+        },
+    }
+}
+
+/// Accumulates features to enable on bootloading
+///
+/// Feature disable bits are only intended to go from 1 to 0. The builder therefore offers no
+/// way to disable a feature: it collects features to enable and clears their bits at once in
+/// [`apply`](FeatureBuilder::apply), which consumes the builder.
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::feature::{FeatureBuilder, Mask};
+///
+/// // keep the instruction cache next-line prefetcher disabled, enable everything else
+/// let builder = FeatureBuilder::all_except(Mask::ICACHE_NEXT_LINE_PREFETCH);
+/// unsafe { builder.apply() };
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct FeatureBuilder {
+    flags: Mask,
+}
+
+impl FeatureBuilder {
+    /// Builder enabling no feature
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            flags: Mask::empty(),
+        }
+    }
+    /// Builder enabling all features known to [`Mask`] except the given ones
+    #[inline]
+    pub const fn all_except(flags: Mask) -> Self {
+        Self {
+            flags: Mask::all().difference(flags),
+        }
+    }
+    /// Adds features to enable
+    #[inline]
+    pub const fn enable(self, flags: Mask) -> Self {
+        Self {
+            flags: self.flags.union(flags),
+        }
+    }
+    /// Features to be enabled
+    #[inline]
+    pub const fn flags(&self) -> Mask {
+        self.flags
+    }
+    /// Enables the collected features
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn apply(self) {
+        enable(self.flags)
+    }
+}
+
+impl Default for FeatureBuilder {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Workaround of a known erratum
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Workaround {
+    /// Keep the given features disabled
+    KeepDisabled(Mask),
+    /// Software outside of this crate must work around the erratum, as described
+    Software(&'static str),
+}
+
+/// Known erratum of SiFive cores
+#[derive(Clone, Copy, Debug)]
+pub struct Erratum {
+    /// Erratum identifier used by SiFive
+    pub id: &'static str,
+    /// Check if the erratum affects a SiFive hart with the given `marchid` and `mimpid`
+    pub matches: fn(marchid: usize, mimpid: usize) -> bool,
+    /// Workaround of the erratum
+    pub workaround: Workaround,
+}
+
+impl Erratum {
+    /// Check if the erratum affects the identified hart
+    #[inline]
+    pub fn affects(&self, identity: &Identity) -> bool {
+        identity.is_sifive() && (self.matches)(identity.marchid, identity.mimpid)
+    }
+}
+
+// Architecture ID of 7-series cores
+const MARCHID_7_SERIES: usize = (1 << (usize::BITS - 1)) | 7;
+// Architecture ID of U54 cores
+const MARCHID_U54: usize = 1;
+
+/// Known errata with their workarounds
+///
+/// Affected revisions follow the SiFive errata checks of the Linux kernel. None of the listed
+/// errata is worked around by keeping features disabled; errata provided by SiFive support
+/// can be applied with [`apply_workarounds_from`].
+pub const ERRATA: &[Erratum] = &[
+    Erratum {
+        id: "CIP-453",
+        matches: |marchid, mimpid| {
+            marchid == MARCHID_7_SERIES && (mimpid == 0x2018_1004 || mimpid == 0x0020_0504)
+        },
+        workaround: Workaround::Software(
+            "stval of instruction page faults and access faults is not sign extended; \
+             the trap handler must sign extend it",
+        ),
+    },
+    Erratum {
+        id: "CIP-1200",
+        matches: |marchid, mimpid| {
+            (marchid == MARCHID_7_SERIES || marchid == MARCHID_U54)
+                && mimpid & 0xff_ffff <= 0x20_0630
+                && mimpid != 0x120_0626
+        },
+        workaround: Workaround::Software(
+            "SFENCE.VMA with an address operand may not flush the TLB entry; \
+             use SFENCE.VMA without operands",
+        ),
+    },
+];
+
+/// Errata affecting the identified hart
+#[inline]
+pub fn errata(identity: &Identity) -> impl Iterator<Item = &'static Erratum> + '_ {
+    ERRATA
+        .iter()
+        .filter(move |erratum| erratum.affects(identity))
+}
+
+/// Removes features from `flags` that must stay disabled on the identified hart
+///
+/// Errata in [`ERRATA`] with a [`Workaround::KeepDisabled`] workaround remove their features
+/// from the set of features to enable. Errata with a software workaround are not handled by
+/// this function; list them with [`errata`].
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::{feature, register::identity};
+///
+/// let flags = feature::apply_workarounds(feature::Mask::all(), &identity::read());
+/// unsafe { feature::enable(flags) };
+/// ```
+#[inline]
+pub fn apply_workarounds(flags: Mask, identity: &Identity) -> Mask {
+    apply_workarounds_from(ERRATA, flags, identity)
+}
+
+/// Removes features from `flags` that must stay disabled on the identified hart, as listed
+/// by errata in `errata`
+///
+/// Allows platforms to apply errata provided by SiFive support that are not in [`ERRATA`].
+#[inline]
+pub fn apply_workarounds_from(errata: &[Erratum], flags: Mask, identity: &Identity) -> Mask {
+    errata
+        .iter()
+        .filter(|erratum| erratum.affects(identity))
+        .fold(flags, |flags, erratum| match erratum.workaround {
+            Workaround::KeepDisabled(keep) => {
+                debug!("keep {:?} disabled for erratum {}", keep, erratum.id);
+                flags.difference(keep)
+            }
+            Workaround::Software(_) => flags,
+        })
+}
+
+/// Outcome of [`boot_init`]
+///
+/// The report covers the feature disable and bpm registers only. Prefetcher configuration is
+/// not part of the sequence, so nothing about prefetchers is reported.
+#[derive(Clone, Copy, Debug)]
+pub struct BootReport {
+    /// Features kept disabled as workaround of errata
+    pub kept_disabled: Mask,
+    /// Branch prediction mode after the sequence
+    pub bpm: Mbpm,
+    /// Feature disable state after the sequence
+    pub status: FeatureStatus,
+}
+
+/// Runs the SiFive recommended boot sequence of platform features on current hart
+///
+/// The sequence:
+///
+/// 1. enables all features, as Freedom Metal does, except those that must stay disabled
+///    because of [errata](ERRATA) affecting the revision of current hart;
+/// 2. selects dynamic branch direction prediction, the reset default of bpm.
+///
+/// The recommended sequence also configures hardware prefetchers. This step is omitted:
+/// prefetchers of Performance cores keep their reset configuration, as their control
+/// registers are not wrapped by this crate, and 7-series cores only have the instruction
+/// cache next-line prefetcher enabled in step 1 through [`Mask::ICACHE_NEXT_LINE_PREFETCH`].
+///
+/// The function takes no core model: errata are looked up from the identification registers
+/// of current hart, whose core model is reported in [`FeatureStatus::core`], so the report
+/// cannot contradict the hardware.
+///
+/// # Safety
+///
+/// Must run on M mode, once per hart.
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::feature;
+///
+/// let report = unsafe { feature::boot_init() };
+/// log::info!("features: {}, bpm: {}", report.status, report.bpm);
+/// ```
+pub unsafe fn boot_init() -> BootReport {
+    let identity = identity::read();
+    let flags = apply_workarounds(Mask::all(), &identity);
+    let kept_disabled = Mask::all().difference(flags);
+    debug!(
+        "boot init for {:?}, keep {:?} disabled",
+        identity.core_model(),
+        kept_disabled
+    );
+    mfeature::clear_features(Mask::from_bits_retain(!kept_disabled.bits()));
+    mbpm::clear_bdp();
+    BootReport {
+        kept_disabled,
+        bpm: mbpm::read(),
+        status: status(),
+    }
+}
+
+/// Runs [`boot_init`] on all harts in the core complex
+///
+/// Feature disable and bpm registers are per hart. This function sends an IPI with `send_ipi`
+/// to every hart in `harts` other than `current_hart`, runs [`boot_init`] on current hart, and
+/// waits until `acked` reports each remote hart has finished.
+///
+/// The IPI handler of remote harts is expected to call [`boot_init`] and then record the
+/// completion that `acked` observes, e.g. by storing to a per-hart atomic flag with release
+/// ordering. Completion flags must be cleared before calling this function.
+///
+/// Returns the report of current hart.
+///
+/// # Safety
+///
+/// Must run on M mode. The IPI handlers of remote harts must run [`boot_init`] and record their
+/// completion as described above, otherwise this function never returns.
+///
+/// # Example
+///
+/// ```no_run
+/// static BOOT_DONE: [AtomicBool; 4] = [/* false, ... */];
+///
+/// fn on_boot_ipi(hart_id: usize) {
+///     unsafe { feature::boot_init() };
+///     BOOT_DONE[hart_id].store(true, Ordering::Release);
+/// }
+///
+/// let report = unsafe {
+///     feature::boot_init_all(
+///         hart_id,
+///         &[0, 1, 2, 3],
+///         |hart| clint.send_soft(hart),
+///         |hart| BOOT_DONE[hart].load(Ordering::Acquire),
+///     )
+/// };
+/// ```
+pub unsafe fn boot_init_all(
+    current_hart: usize,
+    harts: &[usize],
+    send_ipi: impl Fn(usize),
+    acked: impl Fn(usize) -> bool,
+) -> BootReport {
+    crate::smp::cross_call(current_hart, harts, send_ipi, acked, || boot_init())
+}
+
+/// Boot record handed over to the next boot stage
+///
+/// An M-mode bootloader fills the record after [`boot_init`] and places the encoded bytes in
+/// memory or a devicetree property for the operating system to decode. All fields are stored
+/// as little-endian 64-bit values after an 8-byte header, so that the layout does not depend
+/// on XLEN:
+///
+/// | Offset | Field |
+/// |:-------|:------|
+/// | 0 | magic `SFBR` |
+/// | 4 | version, 16 bits |
+/// | 6 | size of the encoded record in bytes, 16 bits |
+/// | 8 | `mvendorid` |
+/// | 16 | `marchid` |
+/// | 24 | `mimpid` |
+/// | 32 | feature disable register |
+/// | 40 | features kept disabled as errata workaround |
+/// | 48 | bpm register |
+/// | 56 | L1 data cache line size in bytes |
+///
+/// Later versions only append fields. Decoders accept records of newer versions and ignore
+/// the fields they do not know.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BootRecord {
+    /// Vendor ID of the core
+    pub mvendorid: u64,
+    /// Architecture ID of the core
+    pub marchid: u64,
+    /// Implementation ID of the core
+    pub mimpid: u64,
+    /// Value of the feature disable register
+    pub features_disabled: u64,
+    /// Features kept disabled as errata workaround
+    pub kept_disabled: u64,
+    /// Value of the bpm register
+    pub bpm: u64,
+    /// L1 data cache line size in bytes
+    pub cache_line_size: u64,
+}
+
+/// Error decoding a [`BootRecord`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecodeError {
+    /// The buffer does not start with the record magic
+    BadMagic,
+    /// The record version predates the first version of the format
+    UnsupportedVersion,
+    /// The buffer or the recorded size is shorter than the record
+    Truncated,
+}
+
+impl BootRecord {
+    /// Magic number at the start of an encoded record
+    pub const MAGIC: [u8; 4] = *b"SFBR";
+    /// Version of the record format written by this crate
+    pub const VERSION: u16 = 1;
+    /// Size of an encoded record of [`BootRecord::VERSION`] in bytes
+    pub const ENCODED_SIZE: usize = 64;
+
+    /// Builds the record of current hart from the report of [`boot_init`]
+    #[inline]
+    pub fn new(report: &BootReport, identity: &Identity, line: CacheLineSize) -> Self {
+        Self {
+            mvendorid: identity.mvendorid as u64,
+            marchid: identity.marchid as u64,
+            mimpid: identity.mimpid as u64,
+            features_disabled: report.status.disabled.bits() as u64,
+            kept_disabled: report.kept_disabled.bits() as u64,
+            bpm: report.bpm.bits() as u64,
+            cache_line_size: line.bytes() as u64,
+        }
+    }
+
+    /// Encodes the record into `buf`, returning the number of bytes written
+    ///
+    /// Returns None if `buf` is shorter than [`BootRecord::ENCODED_SIZE`].
+    pub fn encode(&self, buf: &mut [u8]) -> Option<usize> {
+        let buf = buf.get_mut(..Self::ENCODED_SIZE)?;
+        buf[0..4].copy_from_slice(&Self::MAGIC);
+        buf[4..6].copy_from_slice(&Self::VERSION.to_le_bytes());
+        buf[6..8].copy_from_slice(&(Self::ENCODED_SIZE as u16).to_le_bytes());
+        let fields = [
+            self.mvendorid,
+            self.marchid,
+            self.mimpid,
+            self.features_disabled,
+            self.kept_disabled,
+            self.bpm,
+            self.cache_line_size,
+        ];
+        for (chunk, field) in buf[8..].chunks_exact_mut(8).zip(fields) {
+            chunk.copy_from_slice(&field.to_le_bytes());
+        }
+        Some(Self::ENCODED_SIZE)
+    }
+
+    /// Decodes a record from `buf`, returning the record and its version
+    pub fn decode(buf: &[u8]) -> Result<(Self, u16), DecodeError> {
+        if buf.len() < 8 {
+            return Err(DecodeError::Truncated);
+        }
+        if buf[0..4] != Self::MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+        let version = u16::from_le_bytes([buf[4], buf[5]]);
+        if version == 0 {
+            return Err(DecodeError::UnsupportedVersion);
+        }
+        let size = u16::from_le_bytes([buf[6], buf[7]]) as usize;
+        if size < Self::ENCODED_SIZE || buf.len() < Self::ENCODED_SIZE {
+            return Err(DecodeError::Truncated);
+        }
+        let field = |index: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(&buf[8 + index * 8..16 + index * 8]);
+            u64::from_le_bytes(bytes)
+        };
+        let record = Self {
+            mvendorid: field(0),
+            marchid: field(1),
+            mimpid: field(2),
+            features_disabled: field(3),
+            kept_disabled: field(4),
+            bpm: field(5),
+            cache_line_size: field(6),
+        };
+        Ok((record, version))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        apply_workarounds_from, errata, BootRecord, DecodeError, Erratum, Mask, Workaround,
+        MARCHID_7_SERIES, MARCHID_U54,
+    };
+    use crate::register::identity::{Identity, SIFIVE_VENDOR_ID};
+
+    fn sifive(marchid: usize, mimpid: usize) -> Identity {
+        Identity {
+            mvendorid: SIFIVE_VENDOR_ID,
+            marchid,
+            mimpid,
+            misa: 0,
+        }
+    }
+
+    fn errata_ids(identity: &Identity) -> ([&'static str; 2], usize) {
+        let mut ids = [""; 2];
+        let mut len = 0;
+        for erratum in errata(identity) {
+            ids[len] = erratum.id;
+            len += 1;
+        }
+        (ids, len)
+    }
+
+    #[test]
+    fn match_known_errata() {
+        assert_eq!(
+            errata_ids(&sifive(MARCHID_7_SERIES, 0x2018_1004)),
+            (["CIP-453", "CIP-1200"], 2)
+        );
+        assert_eq!(
+            errata_ids(&sifive(MARCHID_U54, 0x0020_0630)),
+            (["CIP-1200", ""], 1)
+        );
+        assert_eq!(errata_ids(&sifive(MARCHID_7_SERIES, 0x0120_0626)).1, 0);
+        assert_eq!(errata_ids(&sifive(MARCHID_7_SERIES, 0x0021_0427)).1, 0);
+        let other_vendor = Identity {
+            mvendorid: 0,
+            ..sifive(MARCHID_7_SERIES, 0x2018_1004)
+        };
+        assert_eq!(errata_ids(&other_vendor).1, 0);
+    }
+
+    #[test]
+    fn keep_features_disabled() {
+        const TABLE: &[Erratum] = &[Erratum {
+            id: "TEST",
+            matches: |marchid, mimpid| marchid == MARCHID_7_SERIES && mimpid == 0x0020_0504,
+            workaround: Workaround::KeepDisabled(Mask::ICACHE_NEXT_LINE_PREFETCH),
+        }];
+        assert_eq!(
+            apply_workarounds_from(TABLE, Mask::all(), &sifive(MARCHID_7_SERIES, 0x0020_0504)),
+            Mask::all().difference(Mask::ICACHE_NEXT_LINE_PREFETCH)
+        );
+        assert_eq!(
+            apply_workarounds_from(TABLE, Mask::all(), &sifive(MARCHID_7_SERIES, 0x0021_0427)),
+            Mask::all()
+        );
+    }
+
+    const RECORD: BootRecord = BootRecord {
+        mvendorid: 0x489,
+        marchid: 0x8000_0000_0000_0007,
+        mimpid: 0x0421_0427,
+        features_disabled: 0x200,
+        kept_disabled: 0,
+        bpm: 0,
+        cache_line_size: 64,
+    };
+
+    fn encoded() -> [u8; BootRecord::ENCODED_SIZE] {
+        let mut buf = [0; BootRecord::ENCODED_SIZE];
+        assert_eq!(RECORD.encode(&mut buf), Some(BootRecord::ENCODED_SIZE));
+        buf
+    }
+
+    #[test]
+    fn boot_record_round_trip() {
+        assert_eq!(
+            BootRecord::decode(&encoded()),
+            Ok((RECORD, BootRecord::VERSION))
+        );
+        assert_eq!(RECORD.encode(&mut [0; BootRecord::ENCODED_SIZE - 1]), None);
+    }
+
+    #[test]
+    fn boot_record_rejects_bad_header() {
+        let mut buf = encoded();
+        buf[0] = b'X';
+        assert_eq!(BootRecord::decode(&buf), Err(DecodeError::BadMagic));
+        let mut buf = encoded();
+        buf[4..6].copy_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            BootRecord::decode(&buf),
+            Err(DecodeError::UnsupportedVersion)
+        );
+        let buf = encoded();
+        assert_eq!(
+            BootRecord::decode(&buf[..BootRecord::ENCODED_SIZE - 1]),
+            Err(DecodeError::Truncated)
+        );
+    }
+}
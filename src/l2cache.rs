@@ -0,0 +1,573 @@
+//! SiFive L2 cache controller
+//!
+//! The L2 cache controller is a memory-mapped device shared by all harts of a core complex.
+//! Its base address is given by the platform, e.g. `0x201_0000` on FU540 and FU740 SoCs.
+//!
+//! Registers used by this module:
+//!
+//! | Offset | Register |
+//! |:-------|:---------|
+//! | 0x000 | Config |
+//! | 0x008 | WayEnable |
+//! | 0x040 | ECCInjectError |
+//! | 0x100 | DirECCFix address and count |
+//! | 0x120 | DirECCFail address and count |
+//! | 0x140 | DatECCFix address and count |
+//! | 0x160 | DatECCFail address and count |
+//! | 0x200 | Flush64 |
+//! | 0x240 | Flush32 |
+//! | 0x800 | WayMask0, followed by one 64-bit WayMask register per master |
+use bit_field::BitField;
+use core::arch::asm;
+use core::ptr::{read_volatile, write_volatile};
+
+const CONFIG: usize = 0x000;
+const WAY_ENABLE: usize = 0x008;
+const ECC_INJECT_ERROR: usize = 0x040;
+#[cfg(target_pointer_width = "64")]
+const FLUSH64: usize = 0x200;
+const FLUSH32: usize = 0x240;
+const WAY_MASK: usize = 0x800;
+
+/// Size of cache blocks flushed by the Flush64 and Flush32 registers in bytes
+pub const BLOCK_SIZE: u64 = 64;
+
+/// L2 cache controller register block
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct L2Cache {
+    base: usize,
+}
+
+/// Content of the L2 Config register
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Config {
+    bits: u32,
+}
+
+impl Config {
+    /// Returns the contents of the register as raw bits
+    #[inline]
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+    /// Number of banks
+    #[inline]
+    pub fn banks(&self) -> u32 {
+        self.bits & 0xFF
+    }
+    /// Number of ways per bank
+    #[inline]
+    pub fn ways(&self) -> u32 {
+        (self.bits >> 8) & 0xFF
+    }
+    /// Base-2 logarithm of the number of sets per bank
+    #[inline]
+    pub fn lg_sets(&self) -> u32 {
+        (self.bits >> 16) & 0xFF
+    }
+    /// Base-2 logarithm of the cache block size in bytes
+    #[inline]
+    pub fn lg_block_bytes(&self) -> u32 {
+        self.bits >> 24
+    }
+    /// Decodes the cache geometry
+    #[inline]
+    pub fn geometry(&self) -> L2Geometry {
+        L2Geometry {
+            banks: self.banks(),
+            ways: self.ways(),
+            sets: 1 << self.lg_sets(),
+            block_size: 1 << self.lg_block_bytes(),
+        }
+    }
+}
+
+/// Geometry of the L2 cache, decoded from the Config register
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct L2Geometry {
+    /// Number of banks
+    pub banks: u32,
+    /// Number of ways per bank
+    pub ways: u32,
+    /// Number of sets per bank
+    pub sets: u32,
+    /// Cache block size in bytes
+    pub block_size: u32,
+}
+
+impl L2Geometry {
+    /// Total cache size in bytes
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.banks as usize * self.ways as usize * self.sets as usize * self.block_size as usize
+    }
+}
+
+/// Error changing the number of enabled ways, returned by [`L2Cache::enable_ways`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WayEnableError {
+    /// WayEnable may only increase; `enabled` ways are already enabled
+    Shrink {
+        /// Number of ways currently enabled
+        enabled: u32,
+    },
+    /// The cache has only `ways` ways per bank
+    TooMany {
+        /// Number of ways per bank
+        ways: u32,
+    },
+}
+
+/// Error flushing by physical address, returned by [`L2Cache::flush_line_pa`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlushError {
+    /// The address is not aligned to [`BLOCK_SIZE`]
+    Misaligned,
+    /// The address is beyond the range of the Flush32 register on RV32 targets
+    OutOfRange,
+}
+
+/// Set of L2 cache ways
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(transparent)]
+pub struct WaySet {
+    bits: u64,
+}
+
+impl WaySet {
+    /// Empty set
+    #[inline]
+    pub const fn empty() -> Self {
+        Self { bits: 0 }
+    }
+    /// Set of the first `n` ways
+    #[inline]
+    pub const fn first(n: u32) -> Self {
+        let bits = if n >= 64 { u64::MAX } else { (1 << n) - 1 };
+        Self { bits }
+    }
+    /// Set of ways from raw bits
+    #[inline]
+    pub const fn from_bits(bits: u64) -> Self {
+        Self { bits }
+    }
+    /// Returns the set as raw bits
+    #[inline]
+    pub fn bits(&self) -> u64 {
+        self.bits
+    }
+    /// Check if the set contains `way`
+    #[inline]
+    pub fn contains(&self, way: usize) -> bool {
+        way < 64 && self.bits.get_bit(way)
+    }
+    /// Adds `way` to the set
+    ///
+    /// # Panics
+    ///
+    /// Panics if `way` is not less than 64.
+    #[inline]
+    pub fn insert(&mut self, way: usize) {
+        self.bits.set_bit(way, true);
+    }
+    /// Removes `way` from the set
+    ///
+    /// # Panics
+    ///
+    /// Panics if `way` is not less than 64.
+    #[inline]
+    pub fn remove(&mut self, way: usize) {
+        self.bits.set_bit(way, false);
+    }
+}
+
+/// Error assigning ways to a master, returned by [`L2Cache::partition`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PartitionError {
+    /// The set is empty, leaving the master unable to allocate
+    Empty,
+    /// The set contains ways that are not enabled
+    NotEnabled,
+}
+
+/// Source of ECC events recorded by the L2 cache controller
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EccSource {
+    /// Corrected errors in the directory
+    DirectoryFix,
+    /// Uncorrected errors in the directory
+    DirectoryFail,
+    /// Corrected errors in the data array
+    DataFix,
+    /// Uncorrected errors in the data array
+    DataFail,
+}
+
+impl EccSource {
+    #[inline]
+    fn offset(self) -> usize {
+        match self {
+            EccSource::DirectoryFix => 0x100,
+            EccSource::DirectoryFail => 0x120,
+            EccSource::DataFix => 0x140,
+            EccSource::DataFail => 0x160,
+        }
+    }
+}
+
+/// Last ECC event of a source and number of events recorded
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct EccStatus {
+    /// Physical address of the most recent event
+    pub address: u64,
+    /// Number of events recorded
+    pub count: u32,
+}
+
+/// Storage array an injected ECC error goes to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum EccTarget {
+    /// Data array
+    Data,
+    /// Directory
+    Directory,
+}
+
+impl L2Cache {
+    /// Creates a driver over the register block at `base`
+    ///
+    /// # Safety
+    ///
+    /// `base` must be the address of an L2 cache controller register block, accessible from
+    /// current privilege mode.
+    #[inline]
+    pub const unsafe fn new(base: usize) -> Self {
+        Self { base }
+    }
+
+    /// Returns the base address of the register block
+    #[inline]
+    pub const fn base(&self) -> usize {
+        self.base
+    }
+
+    #[inline]
+    fn read32(&self, offset: usize) -> u32 {
+        unsafe { read_volatile((self.base + offset) as *const u32) }
+    }
+
+    #[inline]
+    unsafe fn write32(&self, offset: usize, value: u32) {
+        write_volatile((self.base + offset) as *mut u32, value)
+    }
+
+    #[inline]
+    fn read64(&self, offset: usize) -> u64 {
+        unsafe { read_volatile((self.base + offset) as *const u64) }
+    }
+
+    #[inline]
+    unsafe fn write64(&self, offset: usize, value: u64) {
+        write_volatile((self.base + offset) as *mut u64, value)
+    }
+
+    /// Reads the Config register
+    #[inline]
+    pub fn config(&self) -> Config {
+        Config {
+            bits: self.read32(CONFIG),
+        }
+    }
+
+    /// Reads the cache geometry from the Config register
+    #[inline]
+    pub fn geometry(&self) -> L2Geometry {
+        self.config().geometry()
+    }
+
+    /// Reads the WayEnable register, the index of the largest way enabled
+    #[inline]
+    pub fn way_enable(&self) -> u32 {
+        self.read32(WAY_ENABLE)
+    }
+
+    /// Writes the WayEnable register
+    ///
+    /// # Safety
+    ///
+    /// The register only increases; ways enabled are taken from the L2 loosely-integrated
+    /// memory, whose contents are lost.
+    #[inline]
+    pub unsafe fn write_way_enable(&self, value: u32) {
+        self.write32(WAY_ENABLE, value)
+    }
+
+    /// Number of ways currently enabled
+    #[inline]
+    pub fn enabled_ways(&self) -> u32 {
+        self.way_enable() + 1
+    }
+
+    /// Enables the first `n` ways of the cache
+    ///
+    /// The WayEnable register may only increase until reset. Early boot code running from the
+    /// L2 loosely-integrated memory grows the cache gradually as it stops using the memory.
+    /// Returns an error without writing the register if `n` is less than the number of ways
+    /// already enabled, or more than the number of ways per bank.
+    ///
+    /// # Safety
+    ///
+    /// Contents of the loosely-integrated memory backing the newly enabled ways are lost.
+    #[inline]
+    pub unsafe fn enable_ways(&self, n: u32) -> Result<(), WayEnableError> {
+        let enabled = self.enabled_ways();
+        let ways = self.config().ways();
+        if n < enabled {
+            return Err(WayEnableError::Shrink { enabled });
+        }
+        if n > ways {
+            return Err(WayEnableError::TooMany { ways });
+        }
+        debug!("enable {} of {} l2 cache ways", n, ways);
+        self.write_way_enable(n - 1);
+        Ok(())
+    }
+
+    /// Reads the ECC event record of `source`
+    #[inline]
+    pub fn ecc_status(&self, source: EccSource) -> EccStatus {
+        let offset = source.offset();
+        let low = self.read32(offset) as u64;
+        let high = self.read32(offset + 0x4) as u64;
+        EccStatus {
+            address: (high << 32) | low,
+            count: self.read32(offset + 0x8),
+        }
+    }
+
+    /// Toggles bit `bit` of the next write to `target`, for testing ECC handling
+    ///
+    /// # Safety
+    ///
+    /// The next write to `target` is corrupted on purpose; the ECC error handling of the platform
+    /// must be prepared to report it.
+    #[inline]
+    pub unsafe fn inject_ecc_error(&self, bit: u8, target: EccTarget) {
+        let target = match target {
+            EccTarget::Data => 0,
+            EccTarget::Directory => 1 << 16,
+        };
+        self.write32(ECC_INJECT_ERROR, target | bit as u32)
+    }
+
+    /// Writes the Flush64 register, flushing the cache block containing physical address `pa`
+    ///
+    /// # Safety
+    ///
+    /// `pa` must be a physical address of cacheable memory behind this L2 cache controller.
+    #[cfg(target_pointer_width = "64")]
+    #[inline]
+    pub unsafe fn write_flush64(&self, pa: u64) {
+        self.write64(FLUSH64, pa)
+    }
+
+    /// Writes the Flush32 register, flushing the cache block containing physical address
+    /// `value << 4`
+    ///
+    /// # Safety
+    ///
+    /// `value << 4` must be a physical address of cacheable memory behind this L2 cache controller.
+    #[inline]
+    pub unsafe fn write_flush32(&self, value: u32) {
+        self.write32(FLUSH32, value)
+    }
+
+    /// Flushes the cache block at physical address `pa` out of the L2 cache
+    ///
+    /// Writes the Flush64 register on RV64 targets, and the Flush32 register on RV32 targets,
+    /// which covers physical addresses below 64 GiB.
+    /// The write is not ordered against other memory accesses; callers fence as needed.
+    ///
+    /// # Safety
+    ///
+    /// `pa` must be a physical address of cacheable memory behind this L2 cache controller, aligned
+    /// to [`BLOCK_SIZE`].
+    #[inline]
+    pub unsafe fn flush_line_pa(&self, pa: u64) -> Result<(), FlushError> {
+        if pa & (BLOCK_SIZE - 1) != 0 {
+            return Err(FlushError::Misaligned);
+        }
+        #[cfg(target_pointer_width = "64")]
+        self.write_flush64(pa);
+        #[cfg(target_pointer_width = "32")]
+        {
+            if pa >> 36 != 0 {
+                return Err(FlushError::OutOfRange);
+            }
+            self.write_flush32((pa >> 4) as u32);
+        }
+        Ok(())
+    }
+
+    /// Flushes a physical address range out of the L2 cache
+    ///
+    /// Flushes each cache block overlapping the range from `start` to `start + len`. The start
+    /// address is rounded down and the end address rounded up to [`BLOCK_SIZE`] boundaries, thus
+    /// data around the range sharing cache blocks with it is flushed as well. Nothing is flushed
+    /// if `len` is zero.
+    ///
+    /// The flushes are surrounded by `fence iorw, iorw`, ordering previous stores to the range
+    /// before the flushes and the flushes before following accesses, e.g. starting a DMA transfer.
+    /// Data still held in L1 data caches is not flushed; flush it first with the L1 cache
+    /// instructions in [`crate::asm`].
+    ///
+    /// On RV32 targets, returns [`FlushError::OutOfRange`] without flushing if the range is
+    /// beyond the Flush32 register.
+    ///
+    /// # Safety
+    ///
+    /// The range from `start` to `start + len` must be physical memory behind this L2 cache
+    /// controller.
+    pub unsafe fn flush_range_pa(&self, start: u64, len: u64) -> Result<(), FlushError> {
+        if len == 0 {
+            return Ok(());
+        }
+        let first = start & !(BLOCK_SIZE - 1);
+        let end = start
+            .checked_add(len)
+            .and_then(|end| end.checked_add(BLOCK_SIZE - 1))
+            .ok_or(FlushError::OutOfRange)?
+            & !(BLOCK_SIZE - 1);
+        #[cfg(target_pointer_width = "32")]
+        if (end - 1) >> 36 != 0 {
+            return Err(FlushError::OutOfRange);
+        }
+        asm!("fence iorw, iorw", options(nostack));
+        for pa in (first..end).step_by(BLOCK_SIZE as usize) {
+            self.flush_line_pa(pa)?;
+        }
+        asm!("fence iorw, iorw", options(nostack));
+        Ok(())
+    }
+
+    /// Flushes the whole L2 cache by eviction
+    ///
+    /// The L2 cache controller has no register to flush the whole cache. This function restricts
+    /// the WayMask register of `master` to one enabled way at a time and loads one word of each
+    /// cache block of a way-sized slice of the eviction region, replacing every block of that way
+    /// with clean data. The previous WayMask value is restored afterwards.
+    ///
+    /// `master` is the WayMask index of the data cache of current hart. `evict` is the address of
+    /// a cacheable, readable region of [`L2Geometry::size`] bytes whose contents are not cached
+    /// by the L2 cache; blocks of the region already in the cache are not loaded again, and
+    /// leave the blocks of the way they would replace in place.
+    ///
+    /// Data still held in L1 data caches is not flushed; flush it first with the L1 cache
+    /// instructions in [`crate::asm`].
+    ///
+    /// # Safety
+    ///
+    /// Other masters must not access cached memory while this function runs, otherwise they
+    /// allocate blocks into ways already flushed. Must run with current hart allowed to change
+    /// WayMask registers, usually on M mode.
+    pub unsafe fn flush_all(&self, master: usize, evict: usize) {
+        let geometry = self.geometry();
+        let block_size = geometry.block_size as usize;
+        let way_size = geometry.banks as usize * geometry.sets as usize * block_size;
+        let saved = self.way_mask(master);
+        debug!("flush l2 cache by eviction from {:#x}", evict);
+        asm!("fence iorw, iorw", options(nostack));
+        for way in 0..self.enabled_ways() {
+            self.write_way_mask(master, 1 << way);
+            asm!("fence iorw, iorw", options(nostack));
+            let start = evict + way as usize * way_size;
+            for addr in (start..start + way_size).step_by(block_size) {
+                read_volatile(addr as *const usize);
+            }
+            asm!("fence iorw, iorw", options(nostack));
+        }
+        self.write_way_mask(master, saved);
+        asm!("fence iorw, iorw", options(nostack));
+    }
+
+    /// Restricts master `master` to allocate into the ways of `ways` only
+    ///
+    /// Each master of the L2 cache, like the instruction and data caches of each hart, the
+    /// front port and DMA masters, has a WayMask register. Giving masters disjoint sets of ways
+    /// reserves those ways for them and limits interference between them; masters still hit
+    /// on blocks in all ways. The assignment of master indices is given by the platform.
+    ///
+    /// Returns an error without writing the register if `ways` is empty or contains ways that
+    /// are not enabled.
+    ///
+    /// # Safety
+    ///
+    /// Must run with current hart allowed to change WayMask registers, usually on M mode.
+    #[inline]
+    pub unsafe fn partition(&self, master: usize, ways: WaySet) -> Result<(), PartitionError> {
+        if ways.bits() == 0 {
+            return Err(PartitionError::Empty);
+        }
+        if ways.bits() & !WaySet::first(self.enabled_ways()).bits() != 0 {
+            return Err(PartitionError::NotEnabled);
+        }
+        debug!("restrict l2 master {} to ways {:#x}", master, ways.bits());
+        self.write_way_mask(master, ways.bits());
+        Ok(())
+    }
+
+    /// Reads the WayMask register of master `index`
+    ///
+    /// Bit `n` set means master `index` may allocate into way `n`. The assignment of master
+    /// indices to harts and ports is given by the platform.
+    #[inline]
+    pub fn way_mask(&self, index: usize) -> u64 {
+        self.read64(WAY_MASK + index * 8)
+    }
+
+    /// Writes the WayMask register of master `index`
+    ///
+    /// # Safety
+    ///
+    /// Must run with current hart allowed to change WayMask registers, usually on M mode. `mask`
+    /// must keep at least one enabled way, so that master `index` can still allocate blocks.
+    #[inline]
+    pub unsafe fn write_way_mask(&self, index: usize, mask: u64) {
+        self.write64(WAY_MASK + index * 8, mask)
+    }
+}
+
+/// Flushes the L1 data cache of current hart and the whole L2 cache
+///
+/// Writes back and invalidates the L1 data cache with CFLUSH.D.L1 x0, orders the write-backs
+/// with `fence iorw, iorw`, flushes the L2 cache with [`L2Cache::flush_all`] and fences again,
+/// so that dirty data of current hart has reached memory when this function returns.
+///
+/// L1 data caches of other harts are not flushed; each hart flushes its own before the L2 cache
+/// is flushed, e.g. in the IPI handler of a shutdown sequence.
+///
+/// # Safety
+///
+/// Same as [`L2Cache::flush_all`] with the same `master` and `evict` arguments.
+///
+/// # Platform support
+///
+/// Requires the CFLUSH.D.L1 full-cache flush instruction, see
+/// [`cflush_d_l1_all`](crate::asm::cflush_d_l1_all).
+#[cfg(cflush_d_l1_all)]
+pub unsafe fn flush_all_caches(l2: &L2Cache, master: usize, evict: usize) {
+    crate::asm::cflush_d_l1_all();
+    asm!("fence iorw, iorw", options(nostack));
+    l2.flush_all(master, evict);
+    asm!("fence iorw, iorw", options(nostack));
+}
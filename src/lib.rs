@@ -4,10 +4,211 @@
 //!
 //! - Access to core SiFive CSRs like bpm and feature disable;
 //! - Access to assemble instructions like CEASE and cache control instructions;
-//! - High level wrapper for handling SiFive platform features.
+//! - High level wrapper for handling SiFive platform features;
+//! - Helpers for powering down SiFive cores;
+//! - Performance monitor event selection;
+//! - Driver for the L2 cache controller;
+//! - Raw register dumps for remote diagnostics.
+//!
+//! # Logging
+//!
+//! With the `log` cargo feature enabled, configuration paths of this crate report what they do
+//! through the [`log`](https://docs.rs/log) crate. Records use the default module path targets:
+//!
+//! - `sifive_core::feature`: features enabled, at debug level;
+//! - `sifive_core::register::mbpm`: writes to the bpm register, at debug level;
+//! - `sifive_core::l2cache`: L2 cache ways enabled, partitioned and flushed, at debug level;
+//! - `sifive_core::asm`: each L1 data cache flush or discard, at trace level.
+//!
+//! When the feature is disabled, no logging code is compiled.
+//!
+//! With the `defmt` cargo feature enabled, register values and other plain data types of this
+//! crate implement [`defmt::Format`](https://docs.rs/defmt/0.3/defmt/trait.Format.html) for
+//! logging over RTT or similar transports.
+//!
+//! # Raw instruction encoding
+//!
+//! Instructions without assembler mnemonics are emitted with the `.insn` directive. With the
+//! `insn-word` cargo feature enabled, they are emitted as raw `.word` constants instead, for
+//! toolchains whose assembler rejects these `.insn` directives. VCIX macros in [`asm::vcix`]
+//! always use assembler mnemonics.
+//!
+//! # RNMI register numbering
+//!
+//! SiFive cores implementing the original RNMI proposal place `mnscratch`, `mnepc`, `mncause`
+//! and `mnstatus` at CSR 0x350 to 0x353. Cores implementing the ratified Smrnmi extension place
+//! them at CSR 0x740, 0x741, 0x742 and 0x744. The `smrnmi` cargo feature selects the ratified
+//! numbering for [`register`] modules, [`asm::nmi_return`] and the [`nmi`] stubs.
+//!
+//! # Core model features
+//!
+//! Cargo features named after SiFive cores (`e20`, `e21`, `e24`, `e31`, `e34`, `e76`, `s21`,
+//! `s51`, `s54`, `s76`, `u54`, `u74`, `p270`, `p550` and `x280`) restrict the cache control
+//! instructions in [`asm`] to those documented as supported on the selected cores. Calling
+//! an unsupported instruction then fails at compile time instead of raising an illegal-instruction
+//! exception at runtime. If several cores are selected, only instructions supported by all of them
+//! are available; without any core feature, all instructions are available.
+//!
+//! Core features should only be selected by the final binary, not by libraries.
 #![no_std]
 
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::debug!($($arg)+);
+    };
+}
+
+#[allow(unused_macros)] // unused when no cache instruction is available
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "log")]
+        log::trace!($($arg)+);
+    };
+}
+
+// I-type instruction with register numbers, as `.insn` or with the `insn-word` feature as `.word`
+#[cfg(not(feature = "insn-word"))]
+macro_rules! insn_i {
+    ($opcode: literal, $funct3: literal, $rd: literal, $rs1: literal, $imm: literal) => {
+        concat!(".insn i ", $opcode, ", ", $funct3, ", x", $rd, ", x", $rs1, ", ", $imm)
+    };
+}
+
+#[cfg(feature = "insn-word")]
+macro_rules! insn_i {
+    ($opcode: literal, $funct3: literal, $rd: literal, $rs1: literal, $imm: literal) => {
+        concat!(
+            ".word ((",
+            $imm,
+            " & 0xFFF) << 20) | (",
+            $rs1,
+            " << 15) | (",
+            $funct3,
+            " << 12) | (",
+            $rd,
+            " << 7) | ",
+            $opcode
+        )
+    };
+}
+
+// RNMI CSR number, legacy SiFive or with the `smrnmi` feature ratified Smrnmi
+#[cfg(not(feature = "smrnmi"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __rnmi_csr {
+    (mnscratch) => {
+        "0x350"
+    };
+    (mnepc) => {
+        "0x351"
+    };
+    (mncause) => {
+        "0x352"
+    };
+    (mnstatus) => {
+        "0x353"
+    };
+}
+
+#[cfg(feature = "smrnmi")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __rnmi_csr {
+    (mnscratch) => {
+        "0x740"
+    };
+    (mnepc) => {
+        "0x741"
+    };
+    (mncause) => {
+        "0x742"
+    };
+    (mnstatus) => {
+        "0x744"
+    };
+}
+
+#[cfg(target_arch = "riscv32")]
+macro_rules! load_frame {
+    ($reg:literal, $index:literal, $base:literal) => {
+        concat!("lw ", $reg, ", ", $index, "*4(", $base, ")")
+    };
+}
+
+#[cfg(target_arch = "riscv64")]
+macro_rules! load_frame {
+    ($reg:literal, $index:literal, $base:literal) => {
+        concat!("ld ", $reg, ", ", $index, "*8(", $base, ")")
+    };
+}
+
+#[cfg(target_arch = "riscv32")]
+macro_rules! store_frame {
+    ($reg:literal, $index:literal, $base:literal) => {
+        concat!("sw ", $reg, ", ", $index, "*4(", $base, ")")
+    };
+}
+
+#[cfg(target_arch = "riscv64")]
+macro_rules! store_frame {
+    ($reg:literal, $index:literal, $base:literal) => {
+        concat!("sd ", $reg, ", ", $index, "*8(", $base, ")")
+    };
+}
+
+// Loads and stores an XLEN sized word at the address held in `$base`
+#[cfg(target_pointer_width = "32")]
+macro_rules! load_word {
+    ($reg:literal, $base:literal) => {
+        concat!("lw ", $reg, ", 0(", $base, ")")
+    };
+}
+
+#[cfg(target_pointer_width = "64")]
+macro_rules! load_word {
+    ($reg:literal, $base:literal) => {
+        concat!("ld ", $reg, ", 0(", $base, ")")
+    };
+}
+
+#[cfg(target_pointer_width = "32")]
+macro_rules! store_word {
+    ($reg:literal, $base:literal) => {
+        concat!("sw ", $reg, ", 0(", $base, ")")
+    };
+}
+
+#[cfg(target_pointer_width = "64")]
+macro_rules! store_word {
+    ($reg:literal, $base:literal) => {
+        concat!("sd ", $reg, ", 0(", $base, ")")
+    };
+}
+
+#[cfg(target_arch = "riscv32")]
+macro_rules! frame_offset {
+    ($index:literal) => {
+        concat!($index, "*4")
+    };
+}
+
+#[cfg(target_arch = "riscv64")]
+macro_rules! frame_offset {
+    ($index:literal) => {
+        concat!($index, "*8")
+    };
+}
+
 pub mod asm;
-#[doc(hidden)] // hide by now, API has not been decided yet
+pub mod dump;
 pub mod feature;
+pub mod l2cache;
+pub mod nmi;
+pub mod opcode;
+pub mod pmu;
+pub mod power;
+pub mod probe;
 pub mod register;
+mod smp;
@@ -0,0 +1,210 @@
+//! Resumable non-maskable interrupt handling
+//!
+//! # Save and restore stubs
+//!
+//! The [`nmi_entry!`](crate::nmi_entry) macro defines a complete RNMI entry shim calling a Rust
+//! handler. For custom entry code, this module provides two assembly stubs for use in the RNMI vector, where no register
+//! may be clobbered before the interrupted context is saved.
+//!
+//! `sifive_core_nmi_save` saves the interrupted context into an [`NmiFrame`] on the NMI stack.
+//! `mnscratch` must hold the top of a per-hart NMI stack. The RNMI vector swaps `sp` with
+//! `mnscratch`, reserves the frame, saves `ra` in it and calls the stub:
+//!
+//! ```text
+//! rnmi_vector:
+//!     csrrw   sp, mnscratch, sp
+//!     addi    sp, sp, -288        // size_of::<NmiFrame>(), 144 on RV32
+//!     sd      ra, 8(sp)           // sw ra, 4(sp) on RV32
+//!     call    sifive_core_nmi_save
+//!     // a0 = sp = pointer to the saved NmiFrame
+//!     call    rust_nmi_handler
+//!     j       sifive_core_nmi_restore
+//! ```
+//!
+//! The save stub stores all other registers, the interrupted `sp`, `mnepc`, `mncause` and
+//! `mnstatus`, sets `mnscratch` back to the top of the NMI stack and returns with `a0` pointing
+//! to the frame.
+//!
+//! `sifive_core_nmi_restore` is jumped to with `sp` pointing to the frame. It resumes the
+//! context held in the frame the same way as [`nmi_return`](crate::asm::nmi_return) does;
+//! changes made to the frame by the handler take effect.
+
+/// Context of the interrupted program on entry to the RNMI handler
+///
+/// The layout is fixed to allow access from assembly code: general purpose register `xN`
+/// is located at word offset N, followed by the `mnepc`, `mncause` and `mnstatus` registers.
+#[repr(C, align(16))]
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct NmiFrame {
+    /// General purpose registers, indexed by register number; the slot of `x0` is unused
+    pub x: [usize; 32],
+    /// Program counter of the interrupted instruction
+    pub mnepc: usize,
+    /// Reason for the NMI
+    pub mncause: usize,
+    /// Privilege mode of the interrupted context
+    pub mnstatus: usize,
+}
+
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+core::arch::global_asm!(
+    ".pushsection .text.sifive_core_nmi_save, \"ax\"",
+    ".global sifive_core_nmi_save",
+    ".p2align 2",
+    "sifive_core_nmi_save:",
+    store_frame!("x3", "3", "sp"),
+    store_frame!("x4", "4", "sp"),
+    store_frame!("x5", "5", "sp"),
+    store_frame!("x6", "6", "sp"),
+    store_frame!("x7", "7", "sp"),
+    store_frame!("x8", "8", "sp"),
+    store_frame!("x9", "9", "sp"),
+    store_frame!("x10", "10", "sp"),
+    store_frame!("x11", "11", "sp"),
+    store_frame!("x12", "12", "sp"),
+    store_frame!("x13", "13", "sp"),
+    store_frame!("x14", "14", "sp"),
+    store_frame!("x15", "15", "sp"),
+    store_frame!("x16", "16", "sp"),
+    store_frame!("x17", "17", "sp"),
+    store_frame!("x18", "18", "sp"),
+    store_frame!("x19", "19", "sp"),
+    store_frame!("x20", "20", "sp"),
+    store_frame!("x21", "21", "sp"),
+    store_frame!("x22", "22", "sp"),
+    store_frame!("x23", "23", "sp"),
+    store_frame!("x24", "24", "sp"),
+    store_frame!("x25", "25", "sp"),
+    store_frame!("x26", "26", "sp"),
+    store_frame!("x27", "27", "sp"),
+    store_frame!("x28", "28", "sp"),
+    store_frame!("x29", "29", "sp"),
+    store_frame!("x30", "30", "sp"),
+    store_frame!("x31", "31", "sp"),
+    concat!("csrr t0, ", __rnmi_csr!(mnscratch)),
+    store_frame!("t0", "2", "sp"),
+    concat!("csrr t0, ", __rnmi_csr!(mnepc)),
+    store_frame!("t0", "32", "sp"),
+    concat!("csrr t0, ", __rnmi_csr!(mncause)),
+    store_frame!("t0", "33", "sp"),
+    concat!("csrr t0, ", __rnmi_csr!(mnstatus)),
+    store_frame!("t0", "34", "sp"),
+    concat!("addi t0, sp, ", frame_offset!("36")),
+    concat!("csrw ", __rnmi_csr!(mnscratch), ", t0"),
+    "mv a0, sp",
+    "ret",
+    ".popsection",
+);
+
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+core::arch::global_asm!(
+    ".pushsection .text.sifive_core_nmi_restore, \"ax\"",
+    ".global sifive_core_nmi_restore",
+    ".p2align 2",
+    "sifive_core_nmi_restore:",
+    "mv a0, sp",
+    load_frame!("t0", "32", "a0"),
+    concat!("csrw ", __rnmi_csr!(mnepc), ", t0"),
+    load_frame!("t0", "34", "a0"),
+    concat!("csrw ", __rnmi_csr!(mnstatus), ", t0"),
+    load_frame!("x1", "1", "a0"),
+    load_frame!("x2", "2", "a0"),
+    load_frame!("x3", "3", "a0"),
+    load_frame!("x4", "4", "a0"),
+    load_frame!("x5", "5", "a0"),
+    load_frame!("x6", "6", "a0"),
+    load_frame!("x7", "7", "a0"),
+    load_frame!("x8", "8", "a0"),
+    load_frame!("x9", "9", "a0"),
+    load_frame!("x11", "11", "a0"),
+    load_frame!("x12", "12", "a0"),
+    load_frame!("x13", "13", "a0"),
+    load_frame!("x14", "14", "a0"),
+    load_frame!("x15", "15", "a0"),
+    load_frame!("x16", "16", "a0"),
+    load_frame!("x17", "17", "a0"),
+    load_frame!("x18", "18", "a0"),
+    load_frame!("x19", "19", "a0"),
+    load_frame!("x20", "20", "a0"),
+    load_frame!("x21", "21", "a0"),
+    load_frame!("x22", "22", "a0"),
+    load_frame!("x23", "23", "a0"),
+    load_frame!("x24", "24", "a0"),
+    load_frame!("x25", "25", "a0"),
+    load_frame!("x26", "26", "a0"),
+    load_frame!("x27", "27", "a0"),
+    load_frame!("x28", "28", "a0"),
+    load_frame!("x29", "29", "a0"),
+    load_frame!("x30", "30", "a0"),
+    load_frame!("x31", "31", "a0"),
+    load_frame!("x10", "10", "a0"),
+    insn_i!(0x73, 0, 0, 0, 0x702),
+    ".popsection",
+);
+
+/// Define the RNMI entry shim calling a Rust handler
+///
+/// This macro takes a path to a handler of type `fn(&mut NmiFrame)` and defines the global
+/// symbol `sifive_core_nmi_entry`. The entry shim uses `mnscratch` as the top of a per-hart
+/// NMI stack, saves the interrupted context into an [`NmiFrame`] on that stack, calls
+/// the handler, and resumes the context in the frame with MNRET. Changes made to the frame
+/// by the handler, e.g. to `mnepc`, take effect on return.
+///
+/// The RNMI trap vector address is fixed by the core configuration; place a jump to
+/// `sifive_core_nmi_entry` there. Before any RNMI is taken, each hart must write the top
+/// of its NMI stack into `mnscratch`; the stack top must be aligned to 16 bytes.
+///
+/// The macro may be invoked at most once in the final binary.
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::nmi::NmiFrame;
+///
+/// fn handle_nmi(frame: &mut NmiFrame) {
+///     log_bus_error(frame.mncause, frame.mnepc);
+/// }
+///
+/// sifive_core::nmi_entry!(handle_nmi);
+/// ```
+#[macro_export]
+macro_rules! nmi_entry {
+    ($handler: path) => {
+        #[export_name = "sifive_core_nmi_handler"]
+        extern "C" fn __sifive_core_nmi_handler(frame: &mut $crate::nmi::NmiFrame) {
+            let handler: fn(&mut $crate::nmi::NmiFrame) = $handler;
+            handler(frame)
+        }
+
+        #[cfg(target_pointer_width = "32")]
+        core::arch::global_asm!(
+            ".pushsection .text.sifive_core_nmi_entry, \"ax\"",
+            ".global sifive_core_nmi_entry",
+            ".p2align 2",
+            "sifive_core_nmi_entry:",
+            concat!("csrrw sp, ", $crate::__rnmi_csr!(mnscratch), ", sp"),
+            "addi sp, sp, -36*4",
+            "sw ra, 1*4(sp)",
+            "call sifive_core_nmi_save",
+            "call sifive_core_nmi_handler",
+            "j sifive_core_nmi_restore",
+            ".popsection",
+        );
+
+        #[cfg(target_pointer_width = "64")]
+        core::arch::global_asm!(
+            ".pushsection .text.sifive_core_nmi_entry, \"ax\"",
+            ".global sifive_core_nmi_entry",
+            ".p2align 2",
+            "sifive_core_nmi_entry:",
+            concat!("csrrw sp, ", $crate::__rnmi_csr!(mnscratch), ", sp"),
+            "addi sp, sp, -36*8",
+            "sd ra, 1*8(sp)",
+            "call sifive_core_nmi_save",
+            "call sifive_core_nmi_handler",
+            "j sifive_core_nmi_restore",
+            ".popsection",
+        );
+    };
+}
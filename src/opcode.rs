@@ -0,0 +1,43 @@
+//! Raw instruction encodings
+//!
+//! Opcodes of the SiFive custom instructions and MNRET, for firmware and hypervisors that
+//! decode or emulate these instructions. An instruction word `insn` encodes instruction X
+//! if `insn & MASK_X == MATCH_X`.
+
+/// CEASE, core halt instruction
+pub const CEASE: u32 = 0x30500073;
+/// Match value of CEASE
+pub const MATCH_CEASE: u32 = 0x30500073;
+/// Mask of CEASE, all bits are fixed
+pub const MASK_CEASE: u32 = 0xFFFFFFFF;
+
+/// CFLUSH.D.L1 x0, L1 data cache full-cache flush instruction
+///
+/// Add `rs1 << 15` to encode CFLUSH.D.L1 rs1.
+pub const CFLUSH_D_L1: u32 = 0xFC000073;
+/// Match value of CFLUSH.D.L1 with any rs1
+pub const MATCH_CFLUSH_D_L1: u32 = 0xFC000073;
+/// Mask of CFLUSH.D.L1, the rs1 field is not fixed
+pub const MASK_CFLUSH_D_L1: u32 = 0xFFF07FFF;
+
+/// CDISCARD.D.L1 x0, L1 data cache full-cache invalidate instruction
+///
+/// Add `rs1 << 15` to encode CDISCARD.D.L1 rs1.
+pub const CDISCARD_D_L1: u32 = 0xFC200073;
+/// Match value of CDISCARD.D.L1 with any rs1
+pub const MATCH_CDISCARD_D_L1: u32 = 0xFC200073;
+/// Mask of CDISCARD.D.L1, the rs1 field is not fixed
+pub const MASK_CDISCARD_D_L1: u32 = 0xFFF07FFF;
+
+/// MNRET, non-maskable interrupt return instruction
+pub const MNRET: u32 = 0x70200073;
+/// Match value of MNRET
+pub const MATCH_MNRET: u32 = 0x70200073;
+/// Mask of MNRET, all bits are fixed
+pub const MASK_MNRET: u32 = 0xFFFFFFFF;
+
+/// Source register field `rs1` of an instruction word
+#[inline]
+pub const fn rs1(insn: u32) -> u32 {
+    (insn >> 15) & 0x1F
+}
@@ -0,0 +1,580 @@
+//! Hardware performance monitor
+//!
+//! SiFive cores select the event counted by a programmable counter `mhpmcounterN` through
+//! the `mhpmeventN` register, which is split into an event class and an event mask:
+//!
+//! - bits 7:0 select the event class;
+//! - bits 55:8 on RV64, or 31:8 on RV32, form a mask of events within that class.
+//!
+//! The counter increments when any event set in the mask occurs. Events of different classes
+//! cannot be combined on one counter.
+//!
+//! [`configure`] programs several counters at once, and [`read_counter`] reads their values.
+//!
+//! # Event catalogs
+//!
+//! Events documented for SiFive 7-series cores (E76, S76 and U74) are listed in [`sifive7`].
+//! Other cores, such as the P550 and X280, define their own event tables, which are not
+//! cataloged by this module; select their events with [`EventSpec::new`] and
+//! [`EventSpec::event`] from the event table of the core manual.
+use core::arch::asm;
+
+/// Event selector value for a `mhpmevent` register
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::pmu::EventSpec;
+///
+/// // class 0 (instruction commit events), integer load and store retired
+/// let spec = EventSpec::new(0).event(1).event(2);
+/// assert_eq!(spec.bits(), 0x600);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(transparent)]
+pub struct EventSpec {
+    bits: usize,
+}
+
+impl EventSpec {
+    /// Width of the event mask in `mhpmevent`
+    ///
+    /// On RV64 the mask spans bits 55:8; bits 63:56 hold the Sscofpmf overflow flag and mode
+    /// filters, see [`overflow`]. On RV32 the mask spans bits 31:8, as the upper half of the
+    /// register, including the Sscofpmf bits, resides in `mhpmeventh`.
+    pub const EVENT_BITS: u32 = if usize::BITS == 64 { 48 } else { 24 };
+
+    /// Event selector of given class with an empty event mask
+    #[inline]
+    pub const fn new(class: u8) -> Self {
+        Self {
+            bits: class as usize,
+        }
+    }
+    /// Adds event of given bit index within the class to the event mask
+    ///
+    /// `index` is the bit position in the event mask, i.e. bit `8 + index` of the register.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not less than [`EventSpec::EVENT_BITS`].
+    #[inline]
+    pub const fn event(self, index: u32) -> Self {
+        assert!(
+            index < Self::EVENT_BITS,
+            "event index beyond the event mask"
+        );
+        Self {
+            bits: self.bits | 1 << (8 + index),
+        }
+    }
+    /// Event class of this selector
+    #[inline]
+    pub const fn class(&self) -> u8 {
+        self.bits as u8
+    }
+    /// Event mask of this selector, shifted down to bit 0
+    #[inline]
+    pub const fn mask(&self) -> usize {
+        self.bits >> 8
+    }
+    /// Value to write into a `mhpmevent` register
+    #[inline]
+    pub const fn bits(&self) -> usize {
+        self.bits
+    }
+}
+
+/// Event within an event class
+///
+/// Implemented by the event catalogs in this module, for use with [`EventSpec`].
+pub trait Event: Copy {
+    /// Event class selected in bits 7:0 of `mhpmevent`
+    fn class(&self) -> u8;
+    /// Bit index of the event within the event mask
+    fn index(&self) -> u32;
+}
+
+impl<E: Event> From<E> for EventSpec {
+    #[inline]
+    fn from(event: E) -> Self {
+        EventSpec::new(event.class()).event(event.index())
+    }
+}
+
+impl EventSpec {
+    /// Adds a cataloged event to the event mask
+    ///
+    /// # Panics
+    ///
+    /// Panics if the event belongs to another class than this selector.
+    #[inline]
+    pub fn and<E: Event>(self, event: E) -> Self {
+        assert_eq!(self.class(), event.class(), "events of different classes");
+        self.event(event.index())
+    }
+}
+
+/// Performance events of SiFive 7-series cores
+///
+/// These events are documented for the E76, S76 and U74 cores. Cores without an MMU,
+/// such as the E76, do not count TLB events. Cores without floating-point units do not
+/// count floating-point events.
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::pmu::{sifive7::MemorySystem, EventSpec};
+///
+/// let spec = EventSpec::from(MemorySystem::InstructionCacheMiss).and(MemorySystem::DataCacheMiss);
+/// ```
+pub mod sifive7 {
+    use super::Event;
+
+    /// Instruction commit events, event class 0
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum InstructionCommit {
+        /// Exception taken
+        ExceptionTaken = 0,
+        /// Integer load instruction retired
+        IntegerLoad = 1,
+        /// Integer store instruction retired
+        IntegerStore = 2,
+        /// Atomic memory operation retired
+        Atomic = 3,
+        /// System instruction retired
+        System = 4,
+        /// Integer arithmetic instruction retired
+        IntegerArithmetic = 5,
+        /// Conditional branch retired
+        ConditionalBranch = 6,
+        /// JAL instruction retired
+        Jal = 7,
+        /// JALR instruction retired
+        Jalr = 8,
+        /// Integer multiplication instruction retired
+        IntegerMultiplication = 9,
+        /// Integer division instruction retired
+        IntegerDivision = 10,
+        /// Floating-point load instruction retired
+        FloatLoad = 11,
+        /// Floating-point store instruction retired
+        FloatStore = 12,
+        /// Floating-point addition retired
+        FloatAddition = 13,
+        /// Floating-point multiplication retired
+        FloatMultiplication = 14,
+        /// Floating-point fused multiply-add retired
+        FloatFusedMultiplyAdd = 15,
+        /// Floating-point division or square-root retired
+        FloatDivisionSqrt = 16,
+        /// Other floating-point instruction retired
+        FloatOther = 17,
+    }
+
+    impl Event for InstructionCommit {
+        #[inline]
+        fn class(&self) -> u8 {
+            0
+        }
+        #[inline]
+        fn index(&self) -> u32 {
+            *self as u32
+        }
+    }
+
+    /// Microarchitectural events, event class 1
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum Microarchitectural {
+        /// Load-use interlock
+        LoadUseInterlock = 0,
+        /// Long-latency interlock
+        LongLatencyInterlock = 1,
+        /// CSR read interlock
+        CsrReadInterlock = 2,
+        /// Instruction cache/ITIM busy
+        InstructionCacheBusy = 3,
+        /// Data cache/DTIM busy
+        DataCacheBusy = 4,
+        /// Branch direction misprediction
+        BranchDirectionMisprediction = 5,
+        /// Branch/jump target misprediction
+        BranchTargetMisprediction = 6,
+        /// Pipeline flush from CSR write
+        PipelineFlushCsrWrite = 7,
+        /// Pipeline flush from other event
+        PipelineFlushOther = 8,
+        /// Integer multiplication interlock
+        IntegerMultiplicationInterlock = 9,
+        /// Floating-point interlock
+        FloatInterlock = 10,
+    }
+
+    impl Event for Microarchitectural {
+        #[inline]
+        fn class(&self) -> u8 {
+            1
+        }
+        #[inline]
+        fn index(&self) -> u32 {
+            *self as u32
+        }
+    }
+
+    /// Memory system events, event class 2
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum MemorySystem {
+        /// Instruction cache miss
+        InstructionCacheMiss = 0,
+        /// Data cache miss or memory-mapped I/O access
+        DataCacheMiss = 1,
+        /// Data cache write-back
+        DataCacheWriteBack = 2,
+        /// Instruction TLB miss
+        InstructionTlbMiss = 3,
+        /// Data TLB miss
+        DataTlbMiss = 4,
+        /// UTLB miss
+        UtlbMiss = 5,
+    }
+
+    impl Event for MemorySystem {
+        #[inline]
+        fn class(&self) -> u8 {
+            2
+        }
+        #[inline]
+        fn index(&self) -> u32 {
+            *self as u32
+        }
+    }
+}
+
+/// Index of a programmable counter `mhpmcounterN`, from 3 to 31
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(transparent)]
+pub struct CounterIndex(u8);
+
+impl CounterIndex {
+    /// Programmable counter of given index
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not between 3 and 31.
+    #[inline]
+    pub const fn new(index: u8) -> Self {
+        assert!(index >= 3 && index <= 31);
+        Self(index)
+    }
+    /// Index of the counter
+    #[inline]
+    pub const fn get(&self) -> u8 {
+        self.0
+    }
+    #[inline]
+    const fn bit(&self) -> usize {
+        1 << self.0
+    }
+}
+
+// Accessors of indexed HPM CSRs, whose numbers must be known to the assembler
+macro_rules! hpm_csr {
+    (write $name: ident, $insn: literal, $prefix: literal, $suffix: literal) => {
+        #[inline]
+        unsafe fn $name(index: CounterIndex, value: usize) {
+            hpm_csr!(@write index.get(), value, $insn, $prefix, $suffix,
+                3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31)
+        }
+    };
+    (read $name: ident, $prefix: literal, $suffix: literal) => {
+        #[inline]
+        fn $name(index: CounterIndex) -> usize {
+            let ans: usize;
+            hpm_csr!(@read index.get(), ans, $prefix, $suffix,
+                3 4 5 6 7 8 9 10 11 12 13 14 15 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31);
+            ans
+        }
+    };
+    (@write $index: expr, $value: expr, $insn: literal, $prefix: literal, $suffix: literal, $($n: literal)+) => {
+        match $index {
+            $($n => asm!(concat!($insn, " ", $prefix, $n, $suffix, ", {}"), in(reg) $value),)+
+            _ => unreachable!(),
+        }
+    };
+    (@read $index: expr, $ans: ident, $prefix: literal, $suffix: literal, $($n: literal)+) => {
+        match $index {
+            $($n => unsafe { asm!(concat!("csrr {}, ", $prefix, $n, $suffix), out(reg) $ans) },)+
+            _ => unreachable!(),
+        }
+    };
+}
+
+hpm_csr!(write write_event, "csrw", "mhpmevent", "");
+hpm_csr!(write write_counter, "csrw", "mhpmcounter", "");
+hpm_csr!(read read_counter_low, "mhpmcounter", "");
+#[cfg(target_pointer_width = "32")]
+hpm_csr!(write write_counter_high, "csrw", "mhpmcounter", "h");
+#[cfg(target_pointer_width = "32")]
+hpm_csr!(read read_counter_high, "mhpmcounter", "h");
+#[cfg(target_pointer_width = "64")]
+hpm_csr!(write clear_event_bits, "csrc", "mhpmevent", "");
+#[cfg(target_pointer_width = "32")]
+hpm_csr!(write clear_event_bits_high, "csrc", "mhpmevent", "h");
+
+/// Programs counters to count the given events, starting from zero
+///
+/// For each pair, this function writes the event selector into `mhpmeventN` and clears
+/// `mhpmcounterN`. The counters are inhibited in `mcountinhibit` while being programmed
+/// and released together afterwards, so that they start counting at the same time.
+///
+/// Access of lower privilege modes granted in `mcounteren` is left unchanged; see
+/// [`set_lower_access`] to allow S-mode to read the counters.
+///
+/// # Safety
+///
+/// Must run on M mode.
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::pmu::{self, sifive7::*, CounterIndex, EventSpec};
+///
+/// unsafe {
+///     pmu::configure(&[
+///         (CounterIndex::new(3), MemorySystem::InstructionCacheMiss.into()),
+///         (CounterIndex::new(4), MemorySystem::DataCacheMiss.into()),
+///         (CounterIndex::new(5), Microarchitectural::BranchDirectionMisprediction.into()),
+///         (CounterIndex::new(6), EventSpec::from(InstructionCommit::IntegerLoad).and(InstructionCommit::IntegerStore)),
+///     ])
+/// };
+/// let icache_misses = pmu::read_counter(CounterIndex::new(3));
+/// ```
+pub unsafe fn configure(counters: &[(CounterIndex, EventSpec)]) {
+    let mask = counters
+        .iter()
+        .fold(0, |mask, (index, _)| mask | index.bit());
+    asm!("csrs 0x320, {}", in(reg) mask);
+    for &(index, spec) in counters {
+        write_event(index, spec.bits());
+        write_counter(index, 0);
+        #[cfg(target_pointer_width = "32")]
+        write_counter_high(index, 0);
+    }
+    asm!("csrc 0x320, {}", in(reg) mask);
+}
+
+/// Reads the value of a programmable counter
+///
+/// On RV32, the high and low halves are read repeatedly until a consistent value is obtained.
+#[inline]
+pub fn read_counter(index: CounterIndex) -> u64 {
+    #[cfg(target_pointer_width = "64")]
+    {
+        read_counter_low(index) as u64
+    }
+    #[cfg(target_pointer_width = "32")]
+    loop {
+        let high = read_counter_high(index);
+        let low = read_counter_low(index);
+        if high == read_counter_high(index) {
+            return ((high as u64) << 32) | low as u64;
+        }
+    }
+}
+
+/// Allows or denies lower privilege modes to read a programmable counter
+///
+/// Sets or clears the bit of the counter in `mcounteren`.
+///
+/// # Safety
+///
+/// Must run on M mode.
+#[inline]
+pub unsafe fn set_lower_access(index: CounterIndex, allow: bool) {
+    if allow {
+        asm!("csrs 0x306, {}", in(reg) index.bit())
+    } else {
+        asm!("csrc 0x306, {}", in(reg) index.bit())
+    }
+}
+
+// Bits of programmable counters 3 to 31 in mcountinhibit
+const HPM_COUNTERS: usize = 0xFFFF_FFF8;
+
+/// Stops all programmable counters at once
+///
+/// Sets the bits of counters 3 to 31 in `mcountinhibit` with a single instruction, so that
+/// all counters freeze at the same time.
+///
+/// # Safety
+///
+/// Must run on M mode.
+#[inline]
+pub unsafe fn stop_all() {
+    asm!("csrs 0x320, {}", in(reg) HPM_COUNTERS)
+}
+
+/// Starts all programmable counters at once
+///
+/// Clears the bits of counters 3 to 31 in `mcountinhibit` with a single instruction.
+///
+/// # Safety
+///
+/// Must run on M mode.
+#[inline]
+pub unsafe fn start_all() {
+    asm!("csrc 0x320, {}", in(reg) HPM_COUNTERS)
+}
+
+/// Reads several counters as a consistent snapshot
+///
+/// All programmable counters are frozen while the values are read, so that the events counted
+/// by different counters cover the same instructions. Afterwards `mcountinhibit` is restored,
+/// leaving counters stopped before the call still stopped.
+///
+/// # Safety
+///
+/// Must run on M mode.
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::pmu::{self, CounterIndex};
+///
+/// let [misses, loads] = unsafe { pmu::sample([CounterIndex::new(3), CounterIndex::new(4)]) };
+/// ```
+#[inline]
+pub unsafe fn sample<const N: usize>(counters: [CounterIndex; N]) -> [u64; N] {
+    let previous: usize;
+    asm!("csrrs {}, 0x320, {}", out(reg) previous, in(reg) HPM_COUNTERS);
+    let values = counters.map(read_counter);
+    asm!("csrw 0x320, {}", in(reg) previous);
+    values
+}
+
+/// Sscofpmf counter overflow
+///
+/// On cores implementing the Sscofpmf extension, `mhpmeventN` holds an overflow flag OF at its
+/// most significant bit, in `mhpmeventNh` on RV32. When a counter overflows while its OF bit is
+/// clear, the hart sets OF and raises the local counter overflow interrupt, interrupt code 13,
+/// which is enabled by bit 13 of `mie`. The read-only `scountovf` CSR mirrors the OF bits of
+/// all counters.
+///
+/// A sampling profiler programs each counter with [`configure`], sets it to `2^64 - period`
+/// with [`overflow::write_counter`] to overflow after `period` events, calls [`overflow::arm`]
+/// for it, and calls [`overflow::handle`] from its interrupt handler.
+pub mod overflow {
+    use super::*;
+
+    // Local counter overflow interrupt bit in `mie` and `mip`
+    const LCOFI: usize = 1 << 13;
+
+    /// Clears the overflow flag, so that the next overflow of the counter raises an interrupt
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn arm(index: CounterIndex) {
+        #[cfg(target_pointer_width = "64")]
+        clear_event_bits(index, 1 << 63);
+        #[cfg(target_pointer_width = "32")]
+        clear_event_bits_high(index, 1 << 31);
+    }
+
+    /// Enables the local counter overflow interrupt in `mie`
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode, with a handler for interrupt code 13 installed, e.g. one calling
+    /// [`handle`].
+    #[inline]
+    pub unsafe fn enable_interrupt() {
+        asm!("csrs mie, {}", in(reg) LCOFI)
+    }
+
+    /// Disables the local counter overflow interrupt in `mie`
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn disable_interrupt() {
+        asm!("csrc mie, {}", in(reg) LCOFI)
+    }
+
+    /// Reads the `scountovf` register, holding the overflow flag of counter N at bit N
+    #[inline]
+    pub fn read_scountovf() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0xDA0", out(reg) ans) };
+        ans
+    }
+
+    /// Handles a local counter overflow interrupt
+    ///
+    /// Clears the pending interrupt bit in `mip`, then calls `f` for each programmable counter
+    /// with its overflow flag set and re-arms it with [`arm`] afterwards. `f` may reload the
+    /// counter for the next sampling period.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode, from the handler of interrupt code 13.
+    #[inline]
+    pub unsafe fn handle(mut f: impl FnMut(CounterIndex)) {
+        asm!("csrc mip, {}", in(reg) LCOFI);
+        let overflowed = read_scountovf();
+        for index in 3..32 {
+            if overflowed & (1 << index) != 0 {
+                let index = CounterIndex::new(index);
+                f(index);
+                arm(index);
+            }
+        }
+    }
+
+    /// Writes the value of a programmable counter
+    ///
+    /// On RV32, both halves are written with the counter inhibited.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn write_counter(index: CounterIndex, value: u64) {
+        #[cfg(target_pointer_width = "64")]
+        super::write_counter(index, value as usize);
+        #[cfg(target_pointer_width = "32")]
+        {
+            let previous: usize;
+            asm!("csrrs {}, 0x320, {}", out(reg) previous, in(reg) index.bit());
+            super::write_counter(index, value as usize);
+            write_counter_high(index, (value >> 32) as usize);
+            asm!("csrw 0x320, {}", in(reg) previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EventSpec;
+
+    #[test]
+    fn event_mask_bits() {
+        let last = EventSpec::EVENT_BITS - 1;
+        assert_eq!(EventSpec::new(2).event(0).bits(), 0x102);
+        assert_eq!(EventSpec::new(0).event(last).mask(), 1 << last);
+        assert_eq!(EventSpec::new(0).event(last).bits() >> (usize::BITS - 8), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn event_beyond_mask() {
+        EventSpec::new(0).event(EventSpec::EVENT_BITS);
+    }
+}
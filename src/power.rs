@@ -0,0 +1,242 @@
+//! Power down and idle helpers
+use crate::asm::{cease, cease_with};
+#[cfg(cflush_d_l1_all)]
+use crate::l2cache::L2Cache;
+use core::arch::asm;
+
+/// Run a final action, then cease the current hart
+///
+/// The `finalize` closure is run once on the current hart, its return value is dropped, and
+/// CEASE is executed afterwards. This function never returns.
+///
+/// Typical final actions include printing last log messages, setting a status LED or writing
+/// to a mailbox that signals the power controller.
+///
+/// # Safety
+///
+/// Same as [`cease`], resources still alive on current hart are not freed after the hart ceased.
+/// Resources captured by `finalize` are dropped when it finishes, but any other resource must be
+/// freed by caller before this function is called.
+///
+/// `finalize` runs exactly once; it must not rely on being called again, e.g. to retry a failed
+/// operation, as the hart will not retire another instruction until reset.
+///
+/// # Example
+///
+/// ```no_run
+/// #[panic_handler]
+/// fn machine_panic(info: &PanicInfo) -> ! {
+///     unsafe { sifive_core::power::cease_after(|| print_stack_trace_and_information(info)) }
+/// }
+/// ```
+#[inline]
+pub unsafe fn cease_after<T>(finalize: impl FnOnce() -> T) -> ! {
+    cease_with(|| {
+        let _ = finalize();
+    })
+}
+
+/// Halt current hart, using CEASE where supported
+///
+/// If `cease_supported` is true, this function executes CEASE. Otherwise it masks M-mode
+/// interrupts by clearing `mstatus.MIE` and parks the hart in a WFI loop; the loop guards against
+/// WFI returning early, which is allowed by the RISC-V specification.
+///
+/// Pass whether current core implements CEASE, e.g. from platform configuration, so that
+/// panic handlers and secondary hart parking code can share one halt path across SiFive cores.
+///
+/// # Safety
+///
+/// Same as [`cease`], resources alive on current hart are not freed after the hart halted.
+///
+/// # Privilege mode permissions
+///
+/// Only available in M-mode.
+#[inline]
+pub unsafe fn halt_hart(cease_supported: bool) -> ! {
+    if cease_supported {
+        cease()
+    }
+    asm!("csrci mstatus, 0x8");
+    loop {
+        asm!("wfi");
+    }
+}
+
+/// Flush the cache hierarchy before ceasing current hart
+///
+/// Runs the pre-CEASE sequence for a core complex with an L2 cache, returning once it has
+/// completed. Levels are handled in order:
+///
+/// 1. the L1 data cache of current hart is written back and invalidated with CFLUSH.D.L1 x0;
+/// 2. `fence iorw, iorw` orders the write-backs before the L2 flush;
+/// 3. the whole L2 cache is flushed with [`L2Cache::flush_all`], using `master` and `evict`;
+/// 4. `fence iorw, iorw` orders the L2 flush before the following [`cease`].
+///
+/// L1 instruction caches never hold dirty data and are not flushed. L1 data caches of other
+/// harts are not flushed either; with several harts, each one flushes its own L1 data cache,
+/// and current hart calls this function last, e.g. after [`cease_all`] signalled the others.
+/// Data held in ways of the L2 cache still used as loosely-integrated memory is not written
+/// to memory.
+///
+/// # Safety
+///
+/// Same as [`L2Cache::flush_all`]. Must run on M mode.
+///
+/// # Platform support
+///
+/// Requires the CFLUSH.D.L1 full-cache flush instruction, see
+/// [`cflush_d_l1_all`](crate::asm::cflush_d_l1_all).
+#[cfg(cflush_d_l1_all)]
+#[inline]
+pub unsafe fn prepare_powerdown(l2: &L2Cache, master: usize, evict: usize) {
+    crate::l2cache::flush_all_caches(l2, master, evict)
+}
+
+/// Proof that the power down sequence has been completed on current hart
+///
+/// A token can only be created by `PowerDownToken::prepare`, available on cores with
+/// the CFLUSH.D.L1 full-cache flush instruction, which performs the documented steps before
+/// a hart ceases:
+///
+/// 1. M-mode interrupts are masked by clearing `mstatus.MIE`, so no handler runs afterwards;
+/// 2. the L1 data cache is written back and invalidated with CFLUSH.D.L1 x0;
+/// 3. outstanding memory and I/O transactions are ordered with `fence iorw, iorw`.
+///
+/// The token is consumed by [`cease_safe`]. It is neither `Send` nor `Sync`, so that a token
+/// prepared on one hart cannot be used to cease another.
+#[derive(Debug)]
+pub struct PowerDownToken {
+    _not_send: core::marker::PhantomData<*const ()>,
+}
+
+impl PowerDownToken {
+    /// Run the power down sequence and return the token
+    ///
+    /// Must run on M mode. Requires the CFLUSH.D.L1 full-cache flush instruction,
+    /// see [`cflush_d_l1_all`](crate::asm::cflush_d_l1_all) for platform support.
+    #[cfg(cflush_d_l1_all)]
+    #[inline]
+    pub fn prepare() -> PowerDownToken {
+        unsafe { asm!("csrci mstatus, 0x8") };
+        crate::asm::cflush_d_l1_all();
+        unsafe { asm!("fence iorw, iorw") };
+        PowerDownToken {
+            _not_send: core::marker::PhantomData,
+        }
+    }
+}
+
+/// CEASE current hart after the power down sequence
+///
+/// Consumes a [`PowerDownToken`] proving that interrupts are masked, the L1 data cache
+/// has been flushed and outstanding transactions have been fenced on current hart.
+///
+/// Resources alive on current hart are still not freed, they are leaked as if by
+/// [`core::mem::forget`].
+#[inline]
+pub fn cease_safe(token: PowerDownToken) -> ! {
+    let _ = token;
+    unsafe { cease() }
+}
+
+/// Reason for [`wfi_with_timeout`] to return
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Wakeup {
+    /// The deadline was reached
+    Timeout,
+    /// Another interrupt enabled in `mie` became pending before the deadline
+    Interrupt,
+}
+
+/// Wait for an interrupt or until a deadline on `mtime`
+///
+/// This function reads the current time with `mtime`, programs the timer comparator
+/// to `ticks` later through `set_mtimecmp`, and waits with WFI until either the machine timer
+/// interrupt or another interrupt enabled in `mie` is pending. Spurious wakeups of WFI
+/// are handled by waiting again.
+///
+/// The machine timer interrupt enable bit `mie.MTIE` is set while waiting and restored afterwards.
+/// The comparator is left at the deadline; caller should reprogram it before enabling
+/// the timer interrupt again.
+///
+/// # Safety
+///
+/// Must run on M mode with M-mode interrupts masked in `mstatus.MIE`, so that the pending
+/// interrupt is observed here rather than taken by the trap handler. `set_mtimecmp` must write
+/// the `mtimecmp` register of current hart.
+pub unsafe fn wfi_with_timeout(
+    ticks: u64,
+    mtime: impl Fn() -> u64,
+    set_mtimecmp: impl FnOnce(u64),
+) -> Wakeup {
+    const MTI: usize = 1 << 7;
+    let deadline = mtime().saturating_add(ticks);
+    set_mtimecmp(deadline);
+    let mie: usize;
+    asm!("csrrs {}, mie, {}", out(reg) mie, in(reg) MTI);
+    let ans = loop {
+        asm!("wfi");
+        let mip: usize;
+        asm!("csrr {}, mip", out(reg) mip);
+        let pending = mip & (mie | MTI);
+        if pending & MTI != 0 || mtime() >= deadline {
+            break Wakeup::Timeout;
+        }
+        if pending != 0 {
+            break Wakeup::Interrupt;
+        }
+    };
+    if mie & MTI == 0 {
+        asm!("csrc mie, {}", in(reg) MTI);
+    }
+    ans
+}
+
+/// Cease all harts of the core complex, current hart last
+///
+/// This function sends an IPI with `send_ipi` to every hart in `harts` other than `current_hart`,
+/// waits until `acked` reports each of them has acknowledged, and then ceases current hart.
+///
+/// The IPI handler of remote harts is expected to run its cleanup, record the acknowledgment
+/// that `acked` observes (e.g. by storing to a per-hart atomic flag with release ordering),
+/// and execute [`cease`] without returning. Acknowledgment flags must be cleared before calling
+/// this function.
+///
+/// # Safety
+///
+/// Same as [`cease`], for every hart in `harts`.
+///
+/// # Example
+///
+/// ```no_run
+/// static CEASED: [AtomicBool; 4] = [/* false, ... */];
+///
+/// fn on_cease_ipi(hart_id: usize) -> ! {
+///     unsafe {
+///         sifive_core::asm::cease_with(|| {
+///             cleanup_local_resources();
+///             CEASED[hart_id].store(true, Ordering::Release);
+///         })
+///     }
+/// }
+///
+/// unsafe {
+///     sifive_core::power::cease_all(
+///         hart_id,
+///         &[0, 1, 2, 3],
+///         |hart| clint.send_soft(hart),
+///         |hart| CEASED[hart].load(Ordering::Acquire),
+///     )
+/// }
+/// ```
+pub unsafe fn cease_all(
+    current_hart: usize,
+    harts: &[usize],
+    send_ipi: impl Fn(usize),
+    acked: impl Fn(usize) -> bool,
+) -> ! {
+    crate::smp::cross_call(current_hart, harts, send_ipi, acked, || ());
+    cease()
+}
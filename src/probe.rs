@@ -0,0 +1,148 @@
+//! Runtime probing of SiFive instruction support
+//!
+//! Firmware running across several SiFive cores may detect at runtime which cache control
+//! instructions are implemented, instead of relying on per-core documentation.
+//!
+//! [`probe`] temporarily replaces `mtvec` with a minimal trap handler, executes each candidate
+//! instruction once and records whether it raised an exception. The previous `mtvec`, `mstatus`,
+//! `mepc`, `mcause` and `mtval` are restored before returning.
+//!
+//! CEASE is not probed, as executing it halts the current hart.
+//!
+//! The resulting [`Support`] set, or one built by the user from a known capability table, is
+//! accepted by the fallible `try_*` instruction wrappers in [`asm`](crate::asm), which return
+//! [`NotSupported`](crate::asm::NotSupported) instead of raising an illegal-instruction exception.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+use core::arch::asm;
+
+bitflags::bitflags! {
+    /// Instructions supported by current hart
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct Support: usize {
+        /// CFLUSH.D.L1 x0, L1 data cache full-cache flush
+        const CFLUSH_D_L1_ALL = 1 << 0;
+        /// CFLUSH.D.L1 rs1, L1 data cache flush virtual address
+        const CFLUSH_D_L1_VA = 1 << 1;
+        /// CDISCARD.D.L1 x0, L1 data cache full-cache invalidate
+        const CDISCARD_D_L1_ALL = 1 << 2;
+        /// CDISCARD.D.L1 rs1, L1 data cache invalidate virtual address
+        const CDISCARD_D_L1_VA = 1 << 3;
+        /// CBO.CLEAN from Zicbom extension
+        const CBO_CLEAN = 1 << 4;
+        /// CBO.FLUSH from Zicbom extension
+        const CBO_FLUSH = 1 << 5;
+        /// CBO.INVAL from Zicbom extension
+        const CBO_INVAL = 1 << 6;
+        /// CBO.ZERO from Zicboz extension
+        const CBO_ZERO = 1 << 7;
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Support {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "Support({=usize:#x})", self.bits())
+    }
+}
+
+// Cache block operated on by probes of invalidating and zeroing instructions, its contents
+// are never used. Cache block sizes are not fixed by the CMO extensions; the buffer covers
+// blocks up to a 4 KiB page, so probes never touch memory outside it.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+#[repr(C, align(4096))]
+struct ProbeBlock([u8; 4096]);
+
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+static mut PROBE_BLOCK: ProbeBlock = ProbeBlock([0; 4096]);
+
+// Executes the instructions with t5 holding the probe address, returns true if none trapped
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+macro_rules! executes {
+    ($va: expr, $($insn: expr),+) => {{
+        let trapped: usize;
+        asm!("li t6, 0", $($insn,)+ in("t5") $va, out("t6") trapped);
+        trapped == 0
+    }};
+}
+
+/// Probe which SiFive cache control instructions are supported by current hart
+///
+/// Instructions that invalidate cache lines are only executed on a cache block reserved
+/// for probing, or right after a full-cache flush, so no dirty data is lost.
+///
+/// # Safety
+///
+/// Must run on M mode. This function replaces `mtvec` and masks M-mode interrupts during probing;
+/// caller must ensure no other code relies on trap handling at the same time, e.g. from
+/// an NMI handler or another thread of execution on current hart.
+///
+/// Probed instructions that trap overwrite `mepc`, `mcause` and `mtval`, and the MRET of the
+/// probe trap handler overwrites `mstatus`. These registers are saved before probing and
+/// restored afterwards, so this function may be called from within a trap handler.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+pub unsafe fn probe() -> Support {
+    let mstatus: usize;
+    let mtvec: usize;
+    let mepc: usize;
+    let mcause: usize;
+    let mtval: usize;
+    asm!("csrrci {}, mstatus, 0x8", out(reg) mstatus);
+    asm!("csrr {}, mepc", out(reg) mepc);
+    asm!("csrr {}, mcause", out(reg) mcause);
+    asm!("csrr {}, mtval", out(reg) mtval);
+    asm!("la {0}, sifive_core_probe_trap", "csrrw {0}, mtvec, {0}", out(reg) mtvec);
+    let va = core::ptr::addr_of_mut!(PROBE_BLOCK) as usize;
+    let mut ans = Support::empty();
+    if executes!(va, insn_i!(0x73, 0, 0, 0, -0x40)) {
+        ans |= Support::CFLUSH_D_L1_ALL;
+    }
+    if executes!(va, insn_i!(0x73, 0, 0, 30, -0x40)) {
+        ans |= Support::CFLUSH_D_L1_VA;
+    }
+    if ans.contains(Support::CFLUSH_D_L1_ALL)
+        && executes!(
+            va,
+            insn_i!(0x73, 0, 0, 0, -0x40),
+            insn_i!(0x73, 0, 0, 0, -0x3E)
+        )
+    {
+        ans |= Support::CDISCARD_D_L1_ALL;
+    }
+    if executes!(va, insn_i!(0x73, 0, 0, 30, -0x3E)) {
+        ans |= Support::CDISCARD_D_L1_VA;
+    }
+    if executes!(va, insn_i!(0x0F, 2, 0, 30, 1)) {
+        ans |= Support::CBO_CLEAN;
+    }
+    if executes!(va, insn_i!(0x0F, 2, 0, 30, 2)) {
+        ans |= Support::CBO_FLUSH;
+    }
+    if executes!(va, insn_i!(0x0F, 2, 0, 30, 0)) {
+        ans |= Support::CBO_INVAL;
+    }
+    if executes!(va, insn_i!(0x0F, 2, 0, 30, 4)) {
+        ans |= Support::CBO_ZERO;
+    }
+    asm!("csrw mtvec, {}", in(reg) mtvec);
+    asm!("csrw mtval, {}", in(reg) mtval);
+    asm!("csrw mcause, {}", in(reg) mcause);
+    asm!("csrw mepc, {}", in(reg) mepc);
+    asm!("csrw mstatus, {}", in(reg) mstatus);
+    ans
+}
+
+// Skips the trapping instruction and reports the trap in t6. All probed instructions are
+// 32 bits long.
+#[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+core::arch::global_asm!(
+    ".pushsection .text.sifive_core_probe_trap, \"ax\"",
+    ".global sifive_core_probe_trap",
+    ".p2align 2",
+    "sifive_core_probe_trap:",
+    "csrr t6, mepc",
+    "addi t6, t6, 4",
+    "csrw mepc, t6",
+    "li t6, 1",
+    "mret",
+    ".popsection",
+);
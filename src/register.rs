@@ -1,4 +1,18 @@
 //! Platform specific SiFive CSRs
+//!
+//! # Cache way control
+//!
+//! SiFive cores do not provide a CSR to enable or mask cache ways. On cores with an L2 cache,
+//! ways are enabled and masked through memory-mapped registers of the L2 cache controller
+//! (`WayEnable` and `WayMask`), which are not part of the core CSR space and thus not covered
+//! by this module; see [`crate::l2cache`].
+//!
+//! # Hardware prefetcher control
+//!
+//! SiFive Performance cores have an L1 data cache hardware prefetcher with a custom control CSR.
+//! Its CSR number and field layout differ between core generations and configurations, and are
+//! not given in the public core manuals, so this module does not wrap it. Firmware with access to
+//! the documentation of its core can reach the register through the [`custom`] access functions.
 
 /// Branch prediction mode register
 ///
@@ -10,16 +24,37 @@
 /// While branch predictors are essential to achieve high performance in pipelined processors, they can also cause
 /// undesirable timing variability for hard real-time systems. The bpm register provides a means to customize
 /// the branch predictor behavior to trade average performance for a more predictable execution time
+///
+/// Only the bdp field at bit 0 is defined; the remaining bits are reserved. There is no separate
+/// control for the Return Address Stack or the Branch Target Buffer, the RAS keeps predicting
+/// return addresses regardless of the bpm setting.
 pub mod mbpm {
     use bit_field::BitField;
     use core::arch::asm;
+    use core::fmt;
     /// Branch prediction mode register
+    ///
+    /// No SiFive core manual defines fields beyond bdp, such as RAS or BTB enable controls,
+    /// so none are exposed here. Other bits read back as reserved values and are preserved
+    /// by [`modify`]. The only other branch related control is the short forward branch
+    /// optimization bit of the feature disable register.
     #[derive(Clone, Copy, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     #[repr(transparent)]
     pub struct Mbpm {
         bits: usize,
     }
     impl Mbpm {
+        /// Returns the contents of the register as raw bits
+        #[inline]
+        pub fn bits(&self) -> usize {
+            self.bits
+        }
+        /// Creates a register value from raw bits, e.g. to pass a value read on another hart
+        #[inline]
+        pub fn from_bits(bits: usize) -> Self {
+            Self { bits }
+        }
         /// Branch-Direction Prediction. Determines the value returned by the BHT component of the branch prediction system.
         ///
         /// A zero value indicates dynamic direction prediction, and a non-zero value indicates static-taken direction prediction.
@@ -29,6 +64,22 @@ pub mod mbpm {
         pub fn bdp(&self) -> bool {
             self.bits.get_bit(0)
         }
+        /// Sets the Branch-Direction Prediction field, see [`Mbpm::bdp`]
+        ///
+        /// This only changes the value; use [`write()`] to apply it to the register.
+        #[inline]
+        pub fn set_bdp(&mut self, value: bool) {
+            self.bits.set_bit(0, value);
+        }
+    }
+    impl fmt::Display for Mbpm {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            if self.bdp() {
+                f.write_str("static-taken")
+            } else {
+                f.write_str("dynamic")
+            }
+        }
     }
     /// Reads the register
     #[inline]
@@ -37,19 +88,194 @@ pub mod mbpm {
         unsafe { asm!("csrr {}, 0x7C0", out(reg) bits) };
         Mbpm { bits }
     }
+    /// Writes the register
+    ///
+    /// Values should be obtained from [`read()`] and modified, so that reserved bits keep their values.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn write(value: Mbpm) {
+        debug!("write bpm {:#x}", value.bits);
+        asm!("csrw 0x7C0, {}", in(reg) value.bits)
+    }
+    /// Reads the register, modifies the value with `f` and writes it back
+    ///
+    /// The register is accessed by a single read followed by a single write.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn modify<F: FnOnce(&mut Mbpm)>(f: F) {
+        let mut value = read();
+        f(&mut value);
+        write(value)
+    }
+    /// Clears the Branch Target Buffer without changing the prediction mode
+    ///
+    /// Writes the current register value back to itself, relying on the documented clearing
+    /// of the BTB on any write to bdp. Context switch code may use it to prevent branch
+    /// targets trained by one context from being used by another.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn flush_btb() {
+        debug!("flush btb");
+        asm!("csrr {0}, 0x7C0", "csrw 0x7C0, {0}", out(reg) _)
+    }
+    /// Applies `value` to the bpm register of all harts in the core complex
+    ///
+    /// The branch prediction mode is configured per hart. This function sends an IPI with
+    /// `send_ipi` to every hart in `harts` other than `current_hart`, writes `value` on current
+    /// hart, and waits until `acked` reports each remote hart has acknowledged.
+    ///
+    /// The IPI handler of remote harts is expected to call [`write()`] with the same value and
+    /// then record the acknowledgment that `acked` observes, e.g. by storing to a per-hart atomic
+    /// flag with release ordering. Acknowledgment flags must be cleared before calling this function.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. The IPI handlers of remote harts must write the value and record their
+    /// acknowledgment as described above, otherwise this function never returns.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// static BPM: AtomicUsize = AtomicUsize::new(0);
+    /// static BPM_DONE: [AtomicBool; 4] = [/* false, ... */];
+    ///
+    /// fn on_bpm_ipi(hart_id: usize) {
+    ///     unsafe { mbpm::write(mbpm::Mbpm::from_bits(BPM.load(Ordering::Acquire))) };
+    ///     BPM_DONE[hart_id].store(true, Ordering::Release);
+    /// }
+    ///
+    /// let mut value = mbpm::read();
+    /// value.set_bdp(true);
+    /// BPM.store(value.bits(), Ordering::Release);
+    /// unsafe {
+    ///     mbpm::broadcast(
+    ///         value,
+    ///         hart_id,
+    ///         &[0, 1, 2, 3],
+    ///         |hart| clint.send_soft(hart),
+    ///         |hart| BPM_DONE[hart].load(Ordering::Acquire),
+    ///     )
+    /// }
+    /// ```
+    pub unsafe fn broadcast(
+        value: Mbpm,
+        current_hart: usize,
+        harts: &[usize],
+        send_ipi: impl Fn(usize),
+        acked: impl Fn(usize) -> bool,
+    ) {
+        crate::smp::cross_call(current_hart, harts, send_ipi, acked, || write(value))
+    }
     /// Set mode to dynamic direction prediction.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
     #[inline]
     pub unsafe fn clear_bdp() {
-        asm!("csrci 0x7C0, 0")
+        debug!("set bpm to dynamic direction prediction");
+        asm!("csrci 0x7C0, 1")
     }
     /// Set mode to static-taken direction prediction.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
     #[inline]
     pub unsafe fn set_bdp() {
-        asm!("csrsi 0x7C0, 0")
+        debug!("set bpm to static-taken direction prediction");
+        asm!("csrsi 0x7C0, 1")
+    }
+    /// Set mode to dynamic direction prediction, returning the previous bdp value.
+    ///
+    /// Reads and clears the bit with a single `csrrci` instruction.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn clear_bdp_prev() -> bool {
+        debug!("set bpm to dynamic direction prediction");
+        let bits: usize;
+        asm!("csrrci {}, 0x7C0, 1", out(reg) bits);
+        bits.get_bit(0)
+    }
+    /// Set mode to static-taken direction prediction, returning the previous bdp value.
+    ///
+    /// Reads and sets the bit with a single `csrrsi` instruction.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn set_bdp_prev() -> bool {
+        debug!("set bpm to static-taken direction prediction");
+        let bits: usize;
+        asm!("csrrsi {}, 0x7C0, 1", out(reg) bits);
+        bits.get_bit(0)
+    }
+
+    /// Scoped static-taken branch prediction
+    ///
+    /// On construction the guard switches current hart to static-taken direction prediction,
+    /// and on drop it restores the previous bpm value. Code running while the guard is alive
+    /// trades average performance for a more predictable execution time.
+    ///
+    /// The guard does not disable the instruction cache next-line prefetcher: feature disable
+    /// bits are only intended to be cleared once at boot, and toggling them is not supported.
+    ///
+    /// The guard configures the hart it is created on, thus it is neither `Send` nor `Sync`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// let guard = unsafe { mbpm::DeterministicTimingGuard::new() };
+    /// control_loop_iteration();
+    /// drop(guard);
+    /// ```
+    #[derive(Debug)]
+    pub struct DeterministicTimingGuard {
+        previous: Mbpm,
+        _not_send: core::marker::PhantomData<*const ()>,
+    }
+    impl DeterministicTimingGuard {
+        /// Switches to static-taken direction prediction until the guard is dropped
+        ///
+        /// # Safety
+        ///
+        /// Must run on M mode.
+        #[inline]
+        pub unsafe fn new() -> Self {
+            let previous = read();
+            modify(|r| r.set_bdp(true));
+            Self {
+                previous,
+                _not_send: core::marker::PhantomData,
+            }
+        }
+        /// Register value restored on drop
+        #[inline]
+        pub fn previous(&self) -> Mbpm {
+            self.previous
+        }
+    }
+    impl Drop for DeterministicTimingGuard {
+        #[inline]
+        fn drop(&mut self) {
+            unsafe { write(self.previous) }
+        }
     }
 }
 
-#[doc(hidden)] // hide by now, API has not been decided yet
 /// Feature disable register
 ///
 /// The SiFive custom M-mode Feature Disable CSR is provided to enable or disable certain
@@ -70,12 +296,179 @@ pub mod mbpm {
 pub mod mfeature {
     use crate::feature::Mask;
     use core::arch::asm;
+    use core::fmt;
+
+    /// Feature disable register
+    #[derive(Clone, Copy)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[repr(transparent)]
+    pub struct Mfeature {
+        bits: usize,
+    }
+    impl Mfeature {
+        /// Returns the contents of the register as a feature mask
+        ///
+        /// Bits not known to [`Mask`] are retained in the returned value.
+        #[inline]
+        pub fn mask(&self) -> Mask {
+            Mask::from_bits_retain(self.bits)
+        }
+        /// Data cache clock gating is disabled
+        #[inline]
+        pub fn dcache_clock_gating_disabled(&self) -> bool {
+            self.mask().contains(Mask::DCACHE_CLOCK_GATING)
+        }
+        /// Instruction cache clock gating is disabled
+        #[inline]
+        pub fn icache_clock_gating_disabled(&self) -> bool {
+            self.mask().contains(Mask::ICACHE_CLOCK_GATING)
+        }
+        /// Pipeline clock gating is disabled
+        #[inline]
+        pub fn pipeline_clock_gating_disabled(&self) -> bool {
+            self.mask().contains(Mask::PIPELINE_CLOCK_GATING)
+        }
+        /// Speculative instruction cache refill is disabled
+        #[inline]
+        pub fn speculative_icache_refill_disabled(&self) -> bool {
+            self.mask().contains(Mask::SPECULATIVE_ICACHE_REFILL)
+        }
+        /// Corrupt signal on GrantData messages is suppressed
+        #[inline]
+        pub fn corrupt_signal_grantdata_suppressed(&self) -> bool {
+            self.mask().contains(Mask::CORRUPT_SIGNAL_GRANTDATA)
+        }
+        /// Short forward branch optimization is disabled
+        #[inline]
+        pub fn short_forward_branch_optimize_disabled(&self) -> bool {
+            self.mask().contains(Mask::SHORT_FORWARD_BRANCH_OPTIMIZE)
+        }
+        /// Instruction cache next-line prefetcher is disabled
+        #[inline]
+        pub fn icache_next_line_prefetch_disabled(&self) -> bool {
+            self.mask().contains(Mask::ICACHE_NEXT_LINE_PREFETCH)
+        }
+    }
+    impl fmt::Display for Mfeature {
+        /// Names of disabled features separated by `|`, or `none`
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mask = self.mask();
+            if mask.is_empty() {
+                return f.write_str("none");
+            }
+            let mut first = true;
+            for (name, _) in mask.iter_names() {
+                if !first {
+                    f.write_str("|")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+            let unknown = mask.bits() & !Mask::all().bits();
+            if unknown != 0 {
+                if !first {
+                    f.write_str("|")?;
+                }
+                write!(f, "{:#x}", unknown)?;
+            }
+            Ok(())
+        }
+    }
+    impl fmt::Debug for Mfeature {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Mfeature")
+                .field("bits", &format_args!("{:#x}", self.bits))
+                .field(
+                    "dcache_clock_gating_disabled",
+                    &self.dcache_clock_gating_disabled(),
+                )
+                .field(
+                    "icache_clock_gating_disabled",
+                    &self.icache_clock_gating_disabled(),
+                )
+                .field(
+                    "pipeline_clock_gating_disabled",
+                    &self.pipeline_clock_gating_disabled(),
+                )
+                .field(
+                    "speculative_icache_refill_disabled",
+                    &self.speculative_icache_refill_disabled(),
+                )
+                .field(
+                    "corrupt_signal_grantdata_suppressed",
+                    &self.corrupt_signal_grantdata_suppressed(),
+                )
+                .field(
+                    "short_forward_branch_optimize_disabled",
+                    &self.short_forward_branch_optimize_disabled(),
+                )
+                .field(
+                    "icache_next_line_prefetch_disabled",
+                    &self.icache_next_line_prefetch_disabled(),
+                )
+                .finish()
+        }
+    }
+
+    /// Reads the register
+    #[inline]
+    pub fn read() -> Mfeature {
+        let bits: usize;
+        unsafe { asm!("csrr {}, 0x7C1", out(reg) bits) };
+        Mfeature { bits }
+    }
+
+    /// Returns the feature disable bits implemented on current hart
+    ///
+    /// All implemented bits are set to 1 on reset, and bits of features that cannot be disabled
+    /// are hardwired to zero. The reset value of the register therefore is the set of implemented
+    /// bits. This function returns the current register value as that set.
+    ///
+    /// Setting cleared bits back to 1 is not supported, so implemented bits cannot be detected
+    /// once features were enabled.
+    ///
+    /// # Safety
+    ///
+    /// Must be called on M mode before any feature on current hart is enabled, otherwise the
+    /// returned mask lacks bits that are already cleared.
+    #[inline]
+    pub unsafe fn implemented_bits() -> Mask {
+        read().mask()
+    }
 
     /// Clear corresponding bits in feature register
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
     #[inline]
     pub unsafe fn clear_features(flags: Mask) {
         asm!("csrc 0x7C1, {}", in(reg) flags.bits())
     }
+
+    /// Clear all bits in feature register
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn clear_all() {
+        asm!("csrw 0x7C1, zero")
+    }
+
+    /// Clear corresponding bits in feature register, returning the previous register value
+    ///
+    /// Reads and clears the bits with a single `csrrc` instruction.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn clear_features_prev(flags: Mask) -> Mfeature {
+        let bits: usize;
+        asm!("csrrc {}, 0x7C1, {}", out(reg) bits, in(reg) flags.bits());
+        Mfeature { bits }
+    }
 }
 
 /// Rnmi scratch register
@@ -88,13 +481,33 @@ pub mod mnscratch {
     #[inline]
     pub fn read() -> usize {
         let ans: usize;
-        unsafe { asm!("csrr {}, 0x351", out(reg) ans) };
+        unsafe { asm!(concat!("csrr {}, ", __rnmi_csr!(mnscratch)), out(reg) ans) };
         ans
     }
     /// Writes the `mnscratch` register
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. NMI handler entry code relies on the value of `mnscratch`, which must
+    /// hold what the handler expects; see [`crate::nmi`].
     #[inline]
     pub unsafe fn write(data: usize) {
-        asm!("csrw 0x351, {}", in(reg) data)
+        asm!(concat!("csrw ", __rnmi_csr!(mnscratch), ", {}"), in(reg) data)
+    }
+    /// Swaps the `mnscratch` register with `data`, returning the previous value
+    ///
+    /// The swap is performed by a single `csrrw` instruction, so no NMI can observe an
+    /// intermediate value. Handler entry code written in assembly should use `csrrw`
+    /// directly, as it runs before a stack is available; see [`crate::nmi`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`write()`].
+    #[inline]
+    pub unsafe fn swap(data: usize) -> usize {
+        let ans: usize;
+        asm!(concat!("csrrw {}, ", __rnmi_csr!(mnscratch), ", {}"), out(reg) ans, in(reg) data);
+        ans
     }
 }
 
@@ -109,9 +522,23 @@ pub mod mnepc {
     #[inline]
     pub fn read() -> usize {
         let ans: usize;
-        unsafe { asm!("csrr {}, 0x351", out(reg) ans) };
+        unsafe { asm!(concat!("csrr {}, ", __rnmi_csr!(mnepc)), out(reg) ans) };
         ans
     }
+    /// Writes the `mnepc` register
+    ///
+    /// MNRET resumes execution at the written address, so an NMI handler may use this function
+    /// to skip the interrupted instruction or to redirect the resume point. The lowest bit is
+    /// hardwired to zero and is ignored on write.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode, from an NMI handler. `pc` must be the address of code valid for the
+    /// interrupted context.
+    #[inline]
+    pub unsafe fn write(pc: usize) {
+        asm!(concat!("csrw ", __rnmi_csr!(mnepc), ", {}"), in(reg) pc)
+    }
 }
 
 /// Rnmi cause register
@@ -127,32 +554,87 @@ pub mod mnepc {
 /// | 2 | RNMI input pin | External `rnmi_N` input |
 /// | 3 | Bus error | RNMI caused by BEU |
 pub mod mncause {
+    use bit_field::BitField;
     use core::arch::asm;
+    use core::fmt;
     /// NMI causes
-    #[repr(usize)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
     pub enum Nmi {
-        RnmiInput = 2,
-        BusError = 3,
+        /// External `rnmi_N` input, code 2
+        RnmiInput,
+        /// RNMI caused by BEU, code 3
+        BusError,
+        /// Reserved or vendor specific cause code
+        Other(usize),
+    }
+
+    /// Rnmi cause register
+    #[derive(Clone, Copy, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[repr(transparent)]
+    pub struct Mncause {
+        bits: usize,
+    }
+    impl Mncause {
+        /// Returns the contents of the register as raw bits
+        #[inline]
+        pub fn bits(&self) -> usize {
+            self.bits
+        }
+        /// Check if the interrupt flag at the highest bit is set
+        #[inline]
+        pub fn is_interrupt(&self) -> bool {
+            self.bits.get_bit(usize::BITS as usize - 1)
+        }
+        /// Exception code, i.e. the register value without the interrupt flag
+        #[inline]
+        pub fn code(&self) -> usize {
+            self.bits.get_bits(0..usize::BITS as usize - 1)
+        }
+        /// Decodes the NMI cause, or None if NMI causes are not supported
+        #[inline]
+        pub fn nmi(&self) -> Option<Nmi> {
+            match self.code() {
+                0 => None,
+                2 => Some(Nmi::RnmiInput),
+                3 => Some(Nmi::BusError),
+                code => Some(Nmi::Other(code)),
+            }
+        }
+    }
+
+    impl fmt::Display for Mncause {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.nmi() {
+                None => f.write_str("not supported"),
+                Some(Nmi::RnmiInput) => f.write_str("rnmi input"),
+                Some(Nmi::BusError) => f.write_str("bus error"),
+                Some(Nmi::Other(code)) => write!(f, "cause {}", code),
+            }
+        }
+    }
+
+    /// Reads the register
+    #[inline]
+    pub fn read() -> Mncause {
+        let bits: usize;
+        unsafe { asm!(concat!("csrr {}, ", __rnmi_csr!(mncause)), out(reg) bits) };
+        Mncause { bits }
     }
 
     /// Check if NMI cause is supported
     #[inline]
     pub fn is_supported() -> bool {
         let ans: usize;
-        unsafe { asm!("csrr {}, 0x352", out(reg) ans) };
+        unsafe { asm!(concat!("csrr {}, ", __rnmi_csr!(mncause)), out(reg) ans) };
         ans != 0
     }
 
     /// Reads the NMI cause, or None if not supported
     #[inline]
     pub fn exception_code() -> Option<Nmi> {
-        let ans: usize;
-        unsafe { asm!("csrr {}, 0x352", out(reg) ans) };
-        match ans {
-            2 => Some(Nmi::RnmiInput),
-            3 => Some(Nmi::BusError),
-            _ => None,
-        }
+        read().nmi()
     }
 }
 
@@ -161,7 +643,1615 @@ pub mod mncause {
 /// The mnstatus CSR holds a two-bit field, which, on entry to the trap handler,
 /// holds the privilege mode of the interrupted context encoded in the same manner
 /// as mstatus.mpp.
+///
+/// The field is located at bits 12:11, the same position as mstatus.mpp.
 pub mod mnstatus {
-    // FIXME: this register is located at CSR 0x353, but the specifications did not give
-    // how the bits in this register is defined.
+    use bit_field::BitField;
+    use core::arch::asm;
+    use core::fmt;
+    /// Rnmi status register
+    #[derive(Clone, Copy, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[repr(transparent)]
+    pub struct Mnstatus {
+        bits: usize,
+    }
+    /// Privilege mode of the interrupted context
+    ///
+    /// With the `riscv` cargo feature enabled, this type converts to and from
+    /// `riscv::register::mstatus::MPP`, which uses the same encoding.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum Mnpp {
+        Machine = 3,
+        Supervisor = 1,
+        User = 0,
+    }
+    #[cfg(feature = "riscv")]
+    impl From<Mnpp> for riscv::register::mstatus::MPP {
+        #[inline]
+        fn from(mnpp: Mnpp) -> Self {
+            match mnpp {
+                Mnpp::Machine => Self::Machine,
+                Mnpp::Supervisor => Self::Supervisor,
+                Mnpp::User => Self::User,
+            }
+        }
+    }
+    #[cfg(feature = "riscv")]
+    impl From<riscv::register::mstatus::MPP> for Mnpp {
+        #[inline]
+        fn from(mpp: riscv::register::mstatus::MPP) -> Self {
+            use riscv::register::mstatus::MPP;
+            match mpp {
+                MPP::Machine => Self::Machine,
+                MPP::Supervisor => Self::Supervisor,
+                MPP::User => Self::User,
+            }
+        }
+    }
+    impl Mnstatus {
+        /// Returns the contents of the register as raw bits
+        #[inline]
+        pub fn bits(&self) -> usize {
+            self.bits
+        }
+        /// Previous privilege mode, i.e. the mode the NMI was taken from
+        ///
+        /// Returns None for the reserved encoding 0b10.
+        #[inline]
+        pub fn mnpp(&self) -> Option<Mnpp> {
+            match self.bits.get_bits(11..13) {
+                0b00 => Some(Mnpp::User),
+                0b01 => Some(Mnpp::Supervisor),
+                0b11 => Some(Mnpp::Machine),
+                _ => None,
+            }
+        }
+        /// RNMI enable, defined by the ratified Smrnmi extension at bit 3
+        ///
+        /// Reads as zero on cores with legacy SiFive RNMI registers.
+        #[inline]
+        pub fn nmie(&self) -> bool {
+            self.bits.get_bit(3)
+        }
+    }
+    impl fmt::Display for Mnpp {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(match self {
+                Mnpp::Machine => "machine",
+                Mnpp::Supervisor => "supervisor",
+                Mnpp::User => "user",
+            })
+        }
+    }
+    impl fmt::Display for Mnstatus {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.mnpp() {
+                Some(mnpp) => write!(f, "mnpp: {}", mnpp)?,
+                None => f.write_str("mnpp: reserved")?,
+            }
+            write!(f, ", nmie: {}", self.nmie())
+        }
+    }
+    /// Reads the register
+    #[inline]
+    pub fn read() -> Mnstatus {
+        let bits: usize;
+        unsafe { asm!(concat!("csrr {}, ", __rnmi_csr!(mnstatus)), out(reg) bits) };
+        Mnstatus { bits }
+    }
+    /// Sets the privilege mode MNRET returns to
+    ///
+    /// An NMI handler may return to a different privilege mode than the interrupted one,
+    /// e.g. to enter an M-mode recovery routine written into `mnepc` after a fatal bus error
+    /// in S-mode.
+    ///
+    /// # Safety
+    ///
+    /// The code at `mnepc` must be prepared to run in the given privilege mode.
+    #[inline]
+    pub unsafe fn set_mnpp(mnpp: Mnpp) {
+        asm!(
+            concat!("csrc ", __rnmi_csr!(mnstatus), ", {}"),
+            concat!("csrs ", __rnmi_csr!(mnstatus), ", {}"),
+            in(reg) 0b11usize << 11,
+            in(reg) (mnpp as usize) << 11,
+        )
+    }
+    /// Enables RNMIs by setting the NMIE bit of the ratified Smrnmi `mnstatus` register
+    ///
+    /// Smrnmi clears NMIE on entry to the RNMI handler and MNRET sets it again. This function
+    /// is only available with the `smrnmi` cargo feature.
+    ///
+    /// There is no function to clear NMIE: software writes of zero to NMIE have no effect.
+    /// A handler keeps RNMIs masked while processing by not calling this function.
+    ///
+    /// # Safety
+    ///
+    /// Once NMIE is set, a new RNMI overwrites `mnepc`, `mncause` and `mnstatus`. The handler
+    /// must save these registers before enabling RNMIs if it resumes the interrupted context.
+    #[cfg(feature = "smrnmi")]
+    #[inline]
+    pub unsafe fn set_nmie() {
+        asm!(concat!("csrsi ", __rnmi_csr!(mnstatus), ", 8"))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Mnpp, Mnstatus};
+
+        #[test]
+        fn decode_mnpp() {
+            assert_eq!(Mnstatus { bits: 0b11 << 11 }.mnpp(), Some(Mnpp::Machine));
+            assert_eq!(Mnstatus { bits: 0b01 << 11 }.mnpp(), Some(Mnpp::Supervisor));
+            assert_eq!(Mnstatus { bits: 0 }.mnpp(), Some(Mnpp::User));
+            assert_eq!(Mnstatus { bits: 0b10 << 11 }.mnpp(), None);
+        }
+    }
+}
+
+/// Supervisor trap vector base address register
+///
+/// M-mode firmware may prepare `stvec` before handing off to S-mode, so that the supervisor
+/// software already has a valid trap environment when it starts.
+///
+/// The `BASE` field must be aligned on a 4-byte boundary. The two lowest bits hold the `MODE`
+/// field, where 0 is direct mode and 1 is vectored mode. In vectored mode, `BASE` should
+/// additionally be aligned to cover all interrupt vector entries.
+pub mod stvec {
+    use core::arch::asm;
+    /// Reads the `stvec` register
+    #[inline]
+    pub fn read() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0x105", out(reg) ans) };
+        ans
+    }
+    /// Writes the `stvec` register
+    ///
+    /// # Safety
+    ///
+    /// `data` must hold the address of a trap handler ready to run on S mode, with a valid `MODE`
+    /// field.
+    #[inline]
+    pub unsafe fn write(data: usize) {
+        asm!("csrw 0x105, {}", in(reg) data)
+    }
+}
+
+/// Supervisor scratch register
+///
+/// M-mode firmware may seed `sscratch` with a pointer to the supervisor trap context before
+/// handing off to S-mode.
+pub mod sscratch {
+    use core::arch::asm;
+    /// Reads the `sscratch` register
+    #[inline]
+    pub fn read() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0x140", out(reg) ans) };
+        ans
+    }
+    /// Writes the `sscratch` register
+    ///
+    /// # Safety
+    ///
+    /// The S-mode trap handler relies on the value of `sscratch`, which must hold what the handler
+    /// expects.
+    #[inline]
+    pub unsafe fn write(data: usize) {
+        asm!("csrw 0x140, {}", in(reg) data)
+    }
+}
+
+/// Machine cause register
+///
+/// When a trap is taken into M-mode, mcause holds a code indicating the event that caused
+/// the trap. The highest bit is set if the trap was caused by an interrupt.
+///
+/// In addition to the standard RISC-V interrupts, SiFive cores running in CLINT mode
+/// report core local interrupts with exception codes starting from 16:
+///
+/// | Interrupt code | Interrupt source |
+/// |:---------------|:-----------------|
+/// | 1 | Supervisor software interrupt |
+/// | 3 | Machine software interrupt |
+/// | 5 | Supervisor timer interrupt |
+/// | 7 | Machine timer interrupt |
+/// | 9 | Supervisor external interrupt |
+/// | 11 | Machine external interrupt |
+/// | 16 + N | Local interrupt N |
+///
+/// The number of local interrupts depends on the core configuration.
+pub mod mcause {
+    use bit_field::BitField;
+    use core::arch::asm;
+    use core::fmt;
+    /// Machine cause register
+    #[derive(Clone, Copy, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[repr(transparent)]
+    pub struct Mcause {
+        bits: usize,
+    }
+    /// Interrupt sources
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum Interrupt {
+        SupervisorSoftware,
+        MachineSoftware,
+        SupervisorTimer,
+        MachineTimer,
+        SupervisorExternal,
+        MachineExternal,
+        /// Core local interrupt with its local interrupt number
+        Local(usize),
+    }
+    impl Mcause {
+        /// Creates a register value from raw bits, e.g. `mcause` saved by a trap entry
+        #[inline]
+        pub const fn from_bits(bits: usize) -> Self {
+            Self { bits }
+        }
+        /// Returns the contents of the register as raw bits
+        #[inline]
+        pub fn bits(&self) -> usize {
+            self.bits
+        }
+        /// Check if the trap was caused by an interrupt
+        #[inline]
+        pub fn is_interrupt(&self) -> bool {
+            self.bits.get_bit(usize::BITS as usize - 1)
+        }
+        /// Exception code, i.e. the register value without the interrupt bit
+        #[inline]
+        pub fn code(&self) -> usize {
+            self.bits.get_bits(0..usize::BITS as usize - 1)
+        }
+        /// Decodes the interrupt source, or None if the trap is not caused by a known interrupt
+        #[inline]
+        pub fn interrupt(&self) -> Option<Interrupt> {
+            if !self.is_interrupt() {
+                return None;
+            }
+            match self.code() {
+                1 => Some(Interrupt::SupervisorSoftware),
+                3 => Some(Interrupt::MachineSoftware),
+                5 => Some(Interrupt::SupervisorTimer),
+                7 => Some(Interrupt::MachineTimer),
+                9 => Some(Interrupt::SupervisorExternal),
+                11 => Some(Interrupt::MachineExternal),
+                code if code >= 16 => Some(Interrupt::Local(code - 16)),
+                _ => None,
+            }
+        }
+    }
+    impl fmt::Display for Mcause {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.interrupt() {
+                Some(Interrupt::Local(n)) => write!(f, "local interrupt {}", n),
+                Some(interrupt) => write!(f, "{:?} interrupt", interrupt),
+                None if self.is_interrupt() => write!(f, "interrupt {}", self.code()),
+                None => write!(f, "exception {}", self.code()),
+            }
+        }
+    }
+    /// Reads the register
+    #[inline]
+    pub fn read() -> Mcause {
+        let bits: usize;
+        unsafe { asm!("csrr {}, 0x342", out(reg) bits) };
+        Mcause { bits }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Interrupt, Mcause};
+
+        const INTERRUPT: usize = 1 << (usize::BITS - 1);
+
+        fn interrupt(code: usize) -> Option<Interrupt> {
+            Mcause::from_bits(INTERRUPT | code).interrupt()
+        }
+
+        #[test]
+        fn decode_standard_interrupts() {
+            assert_eq!(interrupt(3), Some(Interrupt::MachineSoftware));
+            assert_eq!(interrupt(7), Some(Interrupt::MachineTimer));
+            assert_eq!(interrupt(9), Some(Interrupt::SupervisorExternal));
+            assert_eq!(interrupt(11), Some(Interrupt::MachineExternal));
+        }
+
+        #[test]
+        fn decode_local_interrupts() {
+            assert_eq!(interrupt(16), Some(Interrupt::Local(0)));
+            assert_eq!(interrupt(17), Some(Interrupt::Local(1)));
+            assert_eq!(interrupt(16 + 47), Some(Interrupt::Local(47)));
+        }
+
+        #[test]
+        fn decode_reserved_interrupts() {
+            assert_eq!(interrupt(0), None);
+            assert_eq!(interrupt(2), None);
+            assert_eq!(interrupt(12), None);
+            assert_eq!(interrupt(15), None);
+        }
+
+        #[test]
+        fn exceptions_are_not_interrupts() {
+            assert_eq!(Mcause::from_bits(11).interrupt(), None);
+            assert_eq!(Mcause::from_bits(16).interrupt(), None);
+        }
+    }
+}
+
+/// Core identification registers
+///
+/// The `mvendorid`, `marchid` and `mimpid` CSRs identify the vendor, the microarchitecture and
+/// the implementation revision of current hart. SiFive cores report JEDEC vendor ID 0x489.
+///
+/// `marchid` identifies a microarchitecture series rather than a product: SiFive 7-series cores
+/// E76, S76 and U74 all report 0x8000_0007 with the most significant bit at XLEN - 1. This module
+/// tells them apart by XLEN and by the presence of S-mode in `misa`. U54 cores, as in FU540 SoCs,
+/// report `marchid` 1 of the Rocket microarchitecture; only RV64 harts with S-mode are decoded as
+/// U54, as other 5-series cores share that value. A `misa` register reading zero carries no ISA
+/// information, so no core model is decoded from it.
+///
+/// E21, E31, S54, P550, P670 and X280 cores are deliberately not decoded, as their `marchid`
+/// values are not documented consistently across core manuals; use the raw values in
+/// [`Identity`](identity::Identity) for these cores.
+pub mod identity {
+    use bit_field::BitField;
+    use core::arch::asm;
+    /// JEDEC manufacturer ID of SiFive, as reported by `mvendorid`
+    pub const SIFIVE_VENDOR_ID: usize = 0x489;
+    /// SiFive core models
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum CoreModel {
+        E76,
+        S76,
+        U54,
+        U74,
+    }
+    /// Identification register values of current hart
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct Identity {
+        /// Vendor ID, `mvendorid`
+        pub mvendorid: usize,
+        /// Architecture ID, `marchid`
+        pub marchid: usize,
+        /// Implementation ID, `mimpid`
+        pub mimpid: usize,
+        /// ISA and extensions, `misa`
+        pub misa: usize,
+    }
+    impl Identity {
+        /// Check if the core is designed by SiFive
+        #[inline]
+        pub fn is_sifive(&self) -> bool {
+            self.mvendorid == SIFIVE_VENDOR_ID
+        }
+        /// Decodes the core model, or None if the core is not known to this module
+        #[inline]
+        pub fn core_model(&self) -> Option<CoreModel> {
+            if !self.is_sifive() || self.misa == 0 {
+                return None;
+            }
+            let has_s_mode = self.misa.get_bit((b'S' - b'A') as usize);
+            if self.marchid == 1 {
+                return if usize::BITS == 64 && has_s_mode {
+                    Some(CoreModel::U54)
+                } else {
+                    None
+                };
+            }
+            if !self.marchid.get_bit(usize::BITS as usize - 1) {
+                return None;
+            }
+            match self.marchid.get_bits(0..usize::BITS as usize - 1) {
+                7 if usize::BITS == 32 => Some(CoreModel::E76),
+                7 if has_s_mode => Some(CoreModel::U74),
+                7 => Some(CoreModel::S76),
+                _ => None,
+            }
+        }
+        /// Implementation revision of the core, the low 24 bits of `mimpid`
+        ///
+        /// SiFive errata apply to ranges of this value, as in the errata checks of the Linux
+        /// kernel. Higher bits of `mimpid` tell variants of a revision apart.
+        #[inline]
+        pub fn revision(&self) -> usize {
+            self.mimpid.get_bits(0..24)
+        }
+    }
+    /// Reads the `mvendorid` register
+    #[inline]
+    pub fn mvendorid() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0xF11", out(reg) ans) };
+        ans
+    }
+    /// Reads the `marchid` register
+    #[inline]
+    pub fn marchid() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0xF12", out(reg) ans) };
+        ans
+    }
+    /// Reads the `mimpid` register
+    #[inline]
+    pub fn mimpid() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0xF13", out(reg) ans) };
+        ans
+    }
+    /// Reads all identification registers of current hart
+    #[inline]
+    pub fn read() -> Identity {
+        let misa: usize;
+        unsafe { asm!("csrr {}, 0x301", out(reg) misa) };
+        Identity {
+            mvendorid: mvendorid(),
+            marchid: marchid(),
+            mimpid: mimpid(),
+            misa,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{CoreModel, Identity, SIFIVE_VENDOR_ID};
+
+        const MISA_I: usize = 1 << (b'I' - b'A');
+        const MISA_S: usize = 1 << (b'S' - b'A');
+        const SERIES_7: usize = (1 << (usize::BITS - 1)) | 7;
+
+        fn core_model(mvendorid: usize, marchid: usize, misa: usize) -> Option<CoreModel> {
+            Identity {
+                mvendorid,
+                marchid,
+                mimpid: 0,
+                misa,
+            }
+            .core_model()
+        }
+
+        #[cfg(target_pointer_width = "64")]
+        #[test]
+        fn decode_core_models() {
+            assert_eq!(
+                core_model(SIFIVE_VENDOR_ID, SERIES_7, MISA_I | MISA_S),
+                Some(CoreModel::U74)
+            );
+            assert_eq!(
+                core_model(SIFIVE_VENDOR_ID, SERIES_7, MISA_I),
+                Some(CoreModel::S76)
+            );
+            assert_eq!(
+                core_model(SIFIVE_VENDOR_ID, 1, MISA_I | MISA_S),
+                Some(CoreModel::U54)
+            );
+        }
+
+        #[test]
+        fn unknown_cores_are_not_decoded() {
+            assert_eq!(core_model(SIFIVE_VENDOR_ID, 1, MISA_I), None);
+            assert_eq!(core_model(SIFIVE_VENDOR_ID, SERIES_7, 0), None);
+            assert_eq!(core_model(SIFIVE_VENDOR_ID, 7, MISA_S), None);
+            assert_eq!(core_model(SIFIVE_VENDOR_ID, 0, MISA_S), None);
+            assert_eq!(core_model(0, SERIES_7, MISA_S), None);
+        }
+
+        #[test]
+        fn revision_from_mimpid() {
+            let identity = |mimpid| Identity {
+                mvendorid: SIFIVE_VENDOR_ID,
+                marchid: SERIES_7,
+                mimpid,
+                misa: MISA_I,
+            };
+            assert_eq!(identity(0x0120_0626).revision(), 0x20_0626);
+            assert_eq!(identity(0x2018_1004).revision(), 0x18_1004);
+        }
+    }
+}
+
+/// Machine configuration pointer register
+///
+/// The `mconfigptr` CSR holds the physical address of a configuration data structure
+/// describing the hart and the platform, or zero if no such structure is provided.
+/// The register is defined since version 1.12 of the privileged architecture; reading it
+/// raises an illegal-instruction exception on older cores.
+///
+/// The format of the configuration structure is not yet specified by RISC-V, and SiFive
+/// cores do not document one, so this crate does not parse it.
+pub mod mconfigptr {
+    use core::arch::asm;
+    /// Reads the `mconfigptr` register
+    #[inline]
+    pub fn read() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0xF15", out(reg) ans) };
+        ans
+    }
+}
+
+/// Access to arbitrary custom CSRs
+///
+/// This module is an escape hatch to SiFive custom CSRs not yet wrapped by this crate. The
+/// [`read`](custom::read), [`write`](custom::write), [`set`](custom::set) and
+/// [`clear`](custom::clear) functions take the CSR number as a const generic parameter and
+/// execute the corresponding CSR instruction. CSR numbers outside the custom ranges defined by
+/// the privileged architecture are rejected at compile time, as well as writes to read-only
+/// custom CSRs.
+///
+/// All functions are unsafe, as accessing a custom CSR may change the behavior of the core in
+/// ways the compiler cannot observe.
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::register::custom;
+///
+/// let features = unsafe { custom::read::<0x7C1>() };
+/// unsafe { custom::clear::<0x7C1>(features) };
+/// ```
+pub mod custom {
+    use core::arch::asm;
+    /// Check if `csr` lies in one of the custom CSR ranges
+    #[inline]
+    pub const fn is_custom(csr: u16) -> bool {
+        matches!(
+            csr,
+            0x800..=0x8FF
+                | 0xCC0..=0xCFF
+                | 0x5C0..=0x5FF
+                | 0x9C0..=0x9FF
+                | 0xDC0..=0xDFF
+                | 0x6C0..=0x6FF
+                | 0xAC0..=0xAFF
+                | 0xEC0..=0xEFF
+                | 0x7C0..=0x7FF
+                | 0xBC0..=0xBFF
+                | 0xFC0..=0xFFF
+        )
+    }
+    /// Check if `csr` lies in one of the writable custom CSR ranges
+    #[inline]
+    pub const fn is_custom_writable(csr: u16) -> bool {
+        is_custom(csr) && (csr >> 10) != 0b11
+    }
+    /// Reads custom CSR `CSR`
+    ///
+    /// # Safety
+    ///
+    /// `CSR` must be implemented by current hart. Reading a custom CSR may have side effects
+    /// defined by the core manual.
+    #[inline]
+    pub unsafe fn read<const CSR: u16>() -> usize {
+        const { assert!(is_custom(CSR), "not a custom CSR") };
+        let ans: usize;
+        asm!("csrr {0}, {csr}", out(reg) ans, csr = const CSR);
+        ans
+    }
+    /// Writes `value` to custom CSR `CSR`
+    ///
+    /// # Safety
+    ///
+    /// `CSR` must be implemented by current hart, and `value` must be valid for it as defined by
+    /// the core manual.
+    #[inline]
+    pub unsafe fn write<const CSR: u16>(value: usize) {
+        const { assert!(is_custom_writable(CSR), "not a writable custom CSR") };
+        asm!("csrw {csr}, {0}", in(reg) value, csr = const CSR);
+    }
+    /// Sets bits of `mask` in custom CSR `CSR`
+    ///
+    /// # Safety
+    ///
+    /// `CSR` must be implemented by current hart, and setting `mask` must be valid for it as
+    /// defined by the core manual.
+    #[inline]
+    pub unsafe fn set<const CSR: u16>(mask: usize) {
+        const { assert!(is_custom_writable(CSR), "not a writable custom CSR") };
+        asm!("csrs {csr}, {0}", in(reg) mask, csr = const CSR);
+    }
+    /// Clears bits of `mask` in custom CSR `CSR`
+    ///
+    /// # Safety
+    ///
+    /// `CSR` must be implemented by current hart, and clearing `mask` must be valid for it as
+    /// defined by the core manual.
+    #[inline]
+    pub unsafe fn clear<const CSR: u16>(mask: usize) {
+        const { assert!(is_custom_writable(CSR), "not a writable custom CSR") };
+        asm!("csrc {csr}, {0}", in(reg) mask, csr = const CSR);
+    }
+}
+
+/// Machine lock world ID register
+///
+/// On cores with SiFive WorldGuard, each hart issues memory transactions tagged with a world
+/// ID (WID). The `mlwid` CSR at 0x390 holds the WID used by the hart in M-mode, and in lower
+/// privilege modes unless WIDs are delegated with `mwiddeleg`.
+///
+/// The WID field is WARL and is log2 of the number of worlds wide; values wider than the
+/// implemented field are truncated. Use [`mlwid::width`] to find the implemented width.
+pub mod mlwid {
+    use core::arch::asm;
+    /// Reads the `mlwid` register
+    #[inline]
+    pub fn read() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0x390", out(reg) ans) };
+        ans
+    }
+    /// Writes the `mlwid` register
+    ///
+    /// Memory accesses following the write are tagged with the new world ID.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. Following memory accesses, including instruction fetches, must be
+    /// permitted for world ID `wid`.
+    #[inline]
+    pub unsafe fn write(wid: usize) {
+        asm!("csrw 0x390, {}", in(reg) wid)
+    }
+    /// Writes the `mlwid` register, returning the previous world ID
+    ///
+    /// Reads and writes the register with a single `csrrw` instruction.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`write()`].
+    #[inline]
+    pub unsafe fn swap(wid: usize) -> usize {
+        let ans: usize;
+        asm!("csrrw {}, 0x390, {}", out(reg) ans, in(reg) wid);
+        ans
+    }
+    /// Number of implemented WID bits
+    ///
+    /// The width is detected by writing all ones to the register and reading back the result;
+    /// the previous value is restored by the immediately following instruction.
+    ///
+    /// # Safety
+    ///
+    /// A trap taken between the two writes would run with the all-ones world ID, thus
+    /// interrupts must be disabled.
+    #[inline]
+    pub unsafe fn width() -> u32 {
+        let ones: usize;
+        asm!(
+            "csrrw {prev}, 0x390, {ones}",
+            "csrrw {ones}, 0x390, {prev}",
+            prev = out(reg) _,
+            ones = inout(reg) usize::MAX => ones,
+            options(nomem, nostack),
+        );
+        usize::BITS - ones.leading_zeros()
+    }
+}
+
+/// World ID delegation register
+///
+/// The `mwiddeleg` CSR at 0x748 holds the set of world IDs that S-mode may select through
+/// `slwid`. Bit N is set if WID N is delegated. If no WID is delegated, lower privilege modes
+/// use the WID in `mlwid`.
+pub mod mwiddeleg {
+    use bit_field::BitField;
+    use core::arch::asm;
+    /// Set of world IDs
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[repr(transparent)]
+    pub struct Wids {
+        bits: usize,
+    }
+    impl Wids {
+        /// Empty set
+        #[inline]
+        pub const fn empty() -> Self {
+            Self { bits: 0 }
+        }
+        /// Set of world IDs from raw bits
+        #[inline]
+        pub const fn from_bits(bits: usize) -> Self {
+            Self { bits }
+        }
+        /// Returns the set as raw bits
+        #[inline]
+        pub fn bits(&self) -> usize {
+            self.bits
+        }
+        /// Check if the set contains `wid`
+        #[inline]
+        pub fn contains(&self, wid: usize) -> bool {
+            wid < usize::BITS as usize && self.bits.get_bit(wid)
+        }
+        /// Adds `wid` to the set
+        ///
+        /// # Panics
+        ///
+        /// Panics if `wid` is not less than XLEN.
+        #[inline]
+        pub fn insert(&mut self, wid: usize) {
+            self.bits.set_bit(wid, true);
+        }
+        /// Removes `wid` from the set
+        ///
+        /// # Panics
+        ///
+        /// Panics if `wid` is not less than XLEN.
+        #[inline]
+        pub fn remove(&mut self, wid: usize) {
+            self.bits.set_bit(wid, false);
+        }
+    }
+    /// Reads the register
+    #[inline]
+    pub fn read() -> Wids {
+        let bits: usize;
+        unsafe { asm!("csrr {}, 0x748", out(reg) bits) };
+        Wids { bits }
+    }
+    /// Writes the register
+    ///
+    /// Bits of world IDs not implemented by the core are ignored.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. Delegated world IDs become selectable by S-mode, which may then access
+    /// memory of these worlds.
+    #[inline]
+    pub unsafe fn write(wids: Wids) {
+        asm!("csrw 0x748, {}", in(reg) wids.bits)
+    }
+}
+
+/// Supervisor lock world ID register
+///
+/// The `slwid` CSR at 0x190 holds the world ID used by the hart in S-mode and U-mode when
+/// world IDs are delegated with `mwiddeleg`. Only delegated WIDs may be written; the register
+/// is accessible from S-mode and from M-mode configuring on behalf of S-mode.
+pub mod slwid {
+    use core::arch::asm;
+    /// Reads the `slwid` register
+    #[inline]
+    pub fn read() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0x190", out(reg) ans) };
+        ans
+    }
+    /// Writes the `slwid` register
+    ///
+    /// `wid` should be contained in the set of delegated world IDs in `mwiddeleg`.
+    ///
+    /// # Safety
+    ///
+    /// Following memory accesses of S-mode and U-mode, including instruction fetches, must be
+    /// permitted for world ID `wid`.
+    #[inline]
+    pub unsafe fn write(wid: usize) {
+        asm!("csrw 0x190, {}", in(reg) wid)
+    }
+    /// Writes the `slwid` register, returning the previous world ID
+    ///
+    /// Reads and writes the register with a single `csrrw` instruction.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`write()`].
+    #[inline]
+    pub unsafe fn swap(wid: usize) -> usize {
+        let ans: usize;
+        asm!("csrrw {}, 0x190, {}", out(reg) ans, in(reg) wid);
+        ans
+    }
+}
+
+/// Machine environment configuration register
+///
+/// The `menvcfg` CSR at 0x30A controls the execution environment of modes below M-mode. Its
+/// cache block operation fields decide whether S-mode and U-mode may execute the Zicbom and
+/// Zicboz instructions in [`crate::asm`]:
+///
+/// - CBIE, bits 5:4, controls CBO.INVAL;
+/// - CBCFE, bit 6, enables CBO.CLEAN and CBO.FLUSH;
+/// - CBZE, bit 7, enables CBO.ZERO.
+///
+/// Disabled instructions raise an illegal-instruction exception below M-mode.
+pub mod menvcfg {
+    use bit_field::BitField;
+    use core::arch::asm;
+    use core::fmt;
+    /// Machine environment configuration register
+    #[derive(Clone, Copy, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[repr(transparent)]
+    pub struct Menvcfg {
+        bits: usize,
+    }
+    /// Cache block invalidate instruction enable
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum Cbie {
+        /// CBO.INVAL raises an illegal-instruction exception
+        IllegalInstruction = 0,
+        /// CBO.INVAL performs a flush operation
+        Flush = 1,
+        /// CBO.INVAL performs an invalidate operation
+        Invalidate = 3,
+    }
+    impl Cbie {
+        #[inline]
+        pub(crate) fn from_bits(bits: usize) -> Option<Self> {
+            match bits {
+                0b00 => Some(Cbie::IllegalInstruction),
+                0b01 => Some(Cbie::Flush),
+                0b11 => Some(Cbie::Invalidate),
+                _ => None,
+            }
+        }
+    }
+    impl Menvcfg {
+        /// Returns the contents of the register as raw bits
+        #[inline]
+        pub fn bits(&self) -> usize {
+            self.bits
+        }
+        /// Cache block invalidate instruction enable
+        ///
+        /// Returns None for the reserved encoding 0b10.
+        #[inline]
+        pub fn cbie(&self) -> Option<Cbie> {
+            Cbie::from_bits(self.bits.get_bits(4..6))
+        }
+        /// Sets cache block invalidate instruction enable
+        #[inline]
+        pub fn set_cbie(&mut self, value: Cbie) {
+            self.bits.set_bits(4..6, value as usize);
+        }
+        /// Cache block clean and flush instruction enable
+        #[inline]
+        pub fn cbcfe(&self) -> bool {
+            self.bits.get_bit(6)
+        }
+        /// Sets cache block clean and flush instruction enable
+        #[inline]
+        pub fn set_cbcfe(&mut self, value: bool) {
+            self.bits.set_bit(6, value);
+        }
+        /// Cache block zero instruction enable
+        #[inline]
+        pub fn cbze(&self) -> bool {
+            self.bits.get_bit(7)
+        }
+        /// Sets cache block zero instruction enable
+        #[inline]
+        pub fn set_cbze(&mut self, value: bool) {
+            self.bits.set_bit(7, value);
+        }
+    }
+    impl fmt::Display for Menvcfg {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.cbie() {
+                Some(cbie) => write!(f, "cbie: {:?}", cbie)?,
+                None => f.write_str("cbie: reserved")?,
+            }
+            write!(f, ", cbcfe: {}, cbze: {}", self.cbcfe(), self.cbze())
+        }
+    }
+    /// Reads the register
+    #[inline]
+    pub fn read() -> Menvcfg {
+        let bits: usize;
+        unsafe { asm!("csrr {}, 0x30A", out(reg) bits) };
+        Menvcfg { bits }
+    }
+    /// Writes the register
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. Allowing lower privilege modes to invalidate cache blocks lets them
+    /// discard dirty data of other software.
+    #[inline]
+    pub unsafe fn write(value: Menvcfg) {
+        asm!("csrw 0x30A, {}", in(reg) value.bits)
+    }
+    /// Reads the register, modifies the value with `f` and writes it back
+    ///
+    /// # Safety
+    ///
+    /// Same as [`write()`].
+    #[inline]
+    pub unsafe fn modify<F: FnOnce(&mut Menvcfg)>(f: F) {
+        let mut value = read();
+        f(&mut value);
+        write(value)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Cbie, Menvcfg};
+
+        #[test]
+        fn decode_cbie() {
+            let mut value = Menvcfg { bits: 0 };
+            assert_eq!(value.cbie(), Some(Cbie::IllegalInstruction));
+            value.set_cbie(Cbie::Invalidate);
+            assert_eq!(value.cbie(), Some(Cbie::Invalidate));
+            assert_eq!(Menvcfg { bits: 0b10 << 4 }.cbie(), None);
+        }
+    }
+}
+
+/// Supervisor environment configuration register
+///
+/// The `senvcfg` CSR at 0x10A controls the execution environment of U-mode. Its cache block
+/// operation fields have the same layout as in [`menvcfg`], and decide whether
+/// U-mode may execute the Zicbom and Zicboz instructions. An instruction is only enabled in
+/// U-mode if it is enabled in both `menvcfg` and `senvcfg`.
+pub mod senvcfg {
+    pub use super::menvcfg::Cbie;
+    use bit_field::BitField;
+    use core::arch::asm;
+    use core::fmt;
+    /// Supervisor environment configuration register
+    #[derive(Clone, Copy, Debug)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    #[repr(transparent)]
+    pub struct Senvcfg {
+        bits: usize,
+    }
+    impl Senvcfg {
+        /// Returns the contents of the register as raw bits
+        #[inline]
+        pub fn bits(&self) -> usize {
+            self.bits
+        }
+        /// Cache block invalidate instruction enable
+        ///
+        /// Returns None for the reserved encoding 0b10.
+        #[inline]
+        pub fn cbie(&self) -> Option<Cbie> {
+            Cbie::from_bits(self.bits.get_bits(4..6))
+        }
+        /// Sets cache block invalidate instruction enable
+        #[inline]
+        pub fn set_cbie(&mut self, value: Cbie) {
+            self.bits.set_bits(4..6, value as usize);
+        }
+        /// Cache block clean and flush instruction enable
+        #[inline]
+        pub fn cbcfe(&self) -> bool {
+            self.bits.get_bit(6)
+        }
+        /// Sets cache block clean and flush instruction enable
+        #[inline]
+        pub fn set_cbcfe(&mut self, value: bool) {
+            self.bits.set_bit(6, value);
+        }
+        /// Cache block zero instruction enable
+        #[inline]
+        pub fn cbze(&self) -> bool {
+            self.bits.get_bit(7)
+        }
+        /// Sets cache block zero instruction enable
+        #[inline]
+        pub fn set_cbze(&mut self, value: bool) {
+            self.bits.set_bit(7, value);
+        }
+    }
+    impl fmt::Display for Senvcfg {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.cbie() {
+                Some(cbie) => write!(f, "cbie: {:?}", cbie)?,
+                None => f.write_str("cbie: reserved")?,
+            }
+            write!(f, ", cbcfe: {}, cbze: {}", self.cbcfe(), self.cbze())
+        }
+    }
+    /// Reads the register
+    #[inline]
+    pub fn read() -> Senvcfg {
+        let bits: usize;
+        unsafe { asm!("csrr {}, 0x10A", out(reg) bits) };
+        Senvcfg { bits }
+    }
+    /// Writes the register
+    ///
+    /// # Safety
+    ///
+    /// Must run on S mode or M mode. Allowing U-mode to invalidate cache blocks lets it discard
+    /// dirty data of other software.
+    #[inline]
+    pub unsafe fn write(value: Senvcfg) {
+        asm!("csrw 0x10A, {}", in(reg) value.bits)
+    }
+    /// Reads the register, modifies the value with `f` and writes it back
+    ///
+    /// # Safety
+    ///
+    /// Same as [`write()`].
+    #[inline]
+    pub unsafe fn modify<F: FnOnce(&mut Senvcfg)>(f: F) {
+        let mut value = read();
+        f(&mut value);
+        write(value)
+    }
+}
+
+/// Debug trigger registers
+///
+/// SiFive cores implement several hardware triggers of the RISC-V debug specification, which
+/// are accessible from M-mode as well as from debug mode. A trigger is selected by writing its
+/// index to `tselect` at 0x7A0, and configured through `tdata1` at 0x7A1 and `tdata2` at 0x7A2.
+///
+/// Address and data match triggers use the `mcontrol` layout (type 2) on older cores and the
+/// `mcontrol6` layout (type 6) on cores implementing version 1.0 of the debug specification.
+/// [`Mcontrol`](trigger::Mcontrol) builds the value for either layout. A trigger with action zero raises a
+/// breakpoint exception, which M-mode firmware handles in its trap handler to implement
+/// breakpoints and watchpoints without an external debugger. Triggers matching in M-mode
+/// only fire while `tcontrol.mte` is set on cores implementing `tcontrol`.
+///
+/// # Example
+///
+/// ```no_run
+/// use sifive_core::register::trigger::{self, Mcontrol};
+///
+/// // watchpoint on stores to a variable
+/// let index = 0;
+/// if unsafe { trigger::count() } > index {
+///     unsafe { trigger::set(index, Mcontrol::new().store().m().s().u(), addr_of_variable) };
+/// }
+/// ```
+pub mod trigger {
+    use bit_field::BitField;
+    use core::arch::asm;
+
+    /// Trigger type of address and data match triggers, debug specification 0.13
+    pub const TYPE_MCONTROL: usize = 2;
+    /// Trigger type of address and data match triggers, debug specification 1.0
+    pub const TYPE_MCONTROL6: usize = 6;
+    /// Trigger type of instruction count triggers
+    pub const TYPE_ICOUNT: usize = 3;
+    /// Trigger type of interrupt triggers
+    pub const TYPE_ITRIGGER: usize = 4;
+    /// Trigger type of exception triggers
+    pub const TYPE_ETRIGGER: usize = 5;
+    /// Trigger type of disabled triggers, debug specification 1.0
+    pub const TYPE_DISABLED: usize = 15;
+
+    /// Address or data match condition of a trigger
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub enum Match {
+        /// Matches the value in `tdata2`
+        Equal = 0,
+        /// Matches the naturally aligned power-of-two range encoded in `tdata2`
+        Napot = 1,
+        /// Matches values greater than or equal to `tdata2`
+        GreaterEqual = 2,
+        /// Matches values less than `tdata2`
+        Less = 3,
+    }
+
+    /// Address and data match trigger configuration
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct Mcontrol {
+        execute: bool,
+        store: bool,
+        load: bool,
+        m: bool,
+        s: bool,
+        u: bool,
+        matches: Match,
+        chain: bool,
+    }
+
+    impl Mcontrol {
+        /// Configuration matching nothing, with action breakpoint exception and equal match
+        #[inline]
+        pub const fn new() -> Self {
+            Self {
+                execute: false,
+                store: false,
+                load: false,
+                m: false,
+                s: false,
+                u: false,
+                matches: Match::Equal,
+                chain: false,
+            }
+        }
+        /// Match the address of executed instructions
+        #[inline]
+        pub const fn execute(mut self) -> Self {
+            self.execute = true;
+            self
+        }
+        /// Match the address of stores
+        #[inline]
+        pub const fn store(mut self) -> Self {
+            self.store = true;
+            self
+        }
+        /// Match the address of loads
+        #[inline]
+        pub const fn load(mut self) -> Self {
+            self.load = true;
+            self
+        }
+        /// Match in M-mode
+        #[inline]
+        pub const fn m(mut self) -> Self {
+            self.m = true;
+            self
+        }
+        /// Match in S-mode
+        #[inline]
+        pub const fn s(mut self) -> Self {
+            self.s = true;
+            self
+        }
+        /// Match in U-mode
+        #[inline]
+        pub const fn u(mut self) -> Self {
+            self.u = true;
+            self
+        }
+        /// Match condition against `tdata2`
+        #[inline]
+        pub const fn matches(mut self, matches: Match) -> Self {
+            self.matches = matches;
+            self
+        }
+        /// Only fire if the next trigger also matches
+        #[inline]
+        pub const fn chain(mut self) -> Self {
+            self.chain = true;
+            self
+        }
+        /// Value of `tdata1` for trigger type [`TYPE_MCONTROL`] or [`TYPE_MCONTROL6`]
+        ///
+        /// # Panics
+        ///
+        /// Panics if `ty` is neither of these types.
+        #[inline]
+        pub fn tdata1(&self, ty: usize) -> usize {
+            assert!(ty == TYPE_MCONTROL || ty == TYPE_MCONTROL6);
+            let mut bits = 0usize;
+            bits.set_bits(usize::BITS as usize - 4..usize::BITS as usize, ty);
+            bits.set_bits(7..11, self.matches as usize);
+            bits.set_bit(11, self.chain);
+            bits.set_bit(6, self.m);
+            bits.set_bit(4, self.s);
+            bits.set_bit(3, self.u);
+            bits.set_bit(2, self.execute);
+            bits.set_bit(1, self.store);
+            bits.set_bit(0, self.load);
+            bits
+        }
+    }
+
+    impl Default for Mcontrol {
+        #[inline]
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    /// Reads the `tselect` register
+    #[inline]
+    pub fn read_tselect() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0x7A0", out(reg) ans) };
+        ans
+    }
+    /// Selects the trigger accessed through the `tdata` registers
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode.
+    #[inline]
+    pub unsafe fn select(index: usize) {
+        asm!("csrw 0x7A0, {}", in(reg) index)
+    }
+    /// Reads the `tdata1` register of the selected trigger
+    #[inline]
+    pub fn read_tdata1() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0x7A1", out(reg) ans) };
+        ans
+    }
+    /// Writes the `tdata1` register of the selected trigger
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. An armed trigger raises breakpoint exceptions, which the M-mode trap
+    /// handler must be prepared to handle.
+    #[inline]
+    pub unsafe fn write_tdata1(value: usize) {
+        asm!("csrw 0x7A1, {}", in(reg) value)
+    }
+    /// Reads the `tdata2` register of the selected trigger
+    #[inline]
+    pub fn read_tdata2() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0x7A2", out(reg) ans) };
+        ans
+    }
+    /// Writes the `tdata2` register of the selected trigger
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. An armed trigger raises breakpoint exceptions, which the M-mode trap
+    /// handler must be prepared to handle.
+    #[inline]
+    pub unsafe fn write_tdata2(value: usize) {
+        asm!("csrw 0x7A2, {}", in(reg) value)
+    }
+    /// Reads the `tinfo` register of the selected trigger, holding bit N if type N is supported
+    ///
+    /// The register is optional; on cores without it, reading raises an illegal-instruction
+    /// exception and the type field of `tdata1` tells the only supported type.
+    #[inline]
+    pub fn read_tinfo() -> usize {
+        let ans: usize;
+        unsafe { asm!("csrr {}, 0x7A4", out(reg) ans) };
+        ans
+    }
+
+    /// Number of triggers on current hart
+    ///
+    /// Triggers are enumerated by writing increasing indices into `tselect` until the value
+    /// does not read back. The selected trigger is undefined afterwards.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. The selected trigger is changed.
+    #[inline]
+    pub unsafe fn count() -> usize {
+        let mut index = 0;
+        loop {
+            select(index);
+            if read_tselect() != index
+                || read_tdata1().get_bits(usize::BITS as usize - 4..usize::BITS as usize) == 0
+            {
+                return index;
+            }
+            index += 1;
+        }
+    }
+
+    // Supported types of the selected trigger as in `tinfo`
+    #[inline]
+    fn supported_types() -> usize {
+        let ty = read_tdata1().get_bits(usize::BITS as usize - 4..usize::BITS as usize);
+        if ty == TYPE_DISABLED {
+            read_tinfo()
+        } else {
+            1 << ty
+        }
+    }
+
+    /// Arms trigger `index` as an address or data match trigger raising breakpoint exceptions
+    ///
+    /// Uses the layout given by the type field of `tdata1`. Disabled triggers of debug
+    /// specification 1.0 report type 15; for them the supported types are read from `tinfo`,
+    /// preferring `mcontrol6`. Returns false without arming if the trigger supports neither type.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. An armed trigger raises breakpoint exceptions, which the M-mode trap
+    /// handler must be prepared to handle.
+    #[inline]
+    pub unsafe fn set(index: usize, config: Mcontrol, tdata2: usize) -> bool {
+        select(index);
+        let types = supported_types();
+        let ty = if types & (1 << TYPE_MCONTROL6) != 0 {
+            TYPE_MCONTROL6
+        } else if types & (1 << TYPE_MCONTROL) != 0 {
+            TYPE_MCONTROL
+        } else {
+            return false;
+        };
+        // disable the trigger while tdata2 is changed
+        write_tdata1(0);
+        write_tdata2(tdata2);
+        write_tdata1(config.tdata1(ty));
+        true
+    }
+
+    /// Disarms trigger `index`
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. The selected trigger is changed.
+    #[inline]
+    pub unsafe fn clear(index: usize) {
+        select(index);
+        write_tdata1(0);
+    }
+
+    /// Instruction count trigger configuration
+    ///
+    /// The trigger decrements its count on every instruction retired in an enabled mode, and
+    /// fires when the count reaches zero, raising a breakpoint exception before the next
+    /// instruction. A count of one single-steps the next instruction.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct Icount {
+        count: usize,
+        m: bool,
+        s: bool,
+        u: bool,
+    }
+
+    impl Icount {
+        /// Fire after `count` instructions, in no privilege mode yet
+        ///
+        /// # Panics
+        ///
+        /// Panics if `count` is zero or does not fit in the 14-bit count field.
+        #[inline]
+        pub const fn new(count: usize) -> Self {
+            assert!(count > 0 && count < 1 << 14);
+            Self {
+                count,
+                m: false,
+                s: false,
+                u: false,
+            }
+        }
+        /// Count instructions retired in M-mode
+        #[inline]
+        pub const fn m(mut self) -> Self {
+            self.m = true;
+            self
+        }
+        /// Count instructions retired in S-mode
+        #[inline]
+        pub const fn s(mut self) -> Self {
+            self.s = true;
+            self
+        }
+        /// Count instructions retired in U-mode
+        #[inline]
+        pub const fn u(mut self) -> Self {
+            self.u = true;
+            self
+        }
+        /// Value of `tdata1` for trigger type [`TYPE_ICOUNT`]
+        #[inline]
+        pub fn tdata1(&self) -> usize {
+            let mut bits = 0usize;
+            bits.set_bits(usize::BITS as usize - 4..usize::BITS as usize, TYPE_ICOUNT);
+            bits.set_bits(10..24, self.count);
+            bits.set_bit(9, self.m);
+            bits.set_bit(7, self.s);
+            bits.set_bit(6, self.u);
+            bits
+        }
+    }
+
+    /// Arms trigger `index` as an instruction count trigger raising a breakpoint exception
+    ///
+    /// Returns false without arming if the trigger does not support type [`TYPE_ICOUNT`].
+    ///
+    /// To single-step lower privilege code, M-mode firmware arms `Icount::new(1).s().u()`
+    /// before MRET. The breakpoint exception, `mcause` 3, is taken after one instruction;
+    /// its handler checks [`icount_hit`] to tell it apart from other breakpoints, and re-arms
+    /// the trigger to continue stepping.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. An armed trigger raises breakpoint exceptions, which the M-mode trap
+    /// handler must be prepared to handle.
+    #[inline]
+    pub unsafe fn set_icount(index: usize, config: Icount) -> bool {
+        select(index);
+        if supported_types() & (1 << TYPE_ICOUNT) == 0 {
+            return false;
+        }
+        write_tdata1(0);
+        write_tdata1(config.tdata1());
+        true
+    }
+
+    /// Check if instruction count trigger `index` has fired
+    ///
+    /// Reads the hit bit of the trigger, which is set by the hardware when the trigger fires.
+    /// The hit bit is optional; cores without it always report false, in which case an icount
+    /// trigger is known to have fired if its count field reads zero.
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. The selected trigger is changed.
+    #[inline]
+    pub unsafe fn icount_hit(index: usize) -> bool {
+        select(index);
+        let tdata1 = read_tdata1();
+        tdata1.get_bits(usize::BITS as usize - 4..usize::BITS as usize) == TYPE_ICOUNT
+            && tdata1.get_bit(24)
+    }
+    /// Exception trigger configuration
+    ///
+    /// The trigger fires when an exception with a cause in the given set is taken from an
+    /// enabled mode. It raises a breakpoint exception once the trap of the matched exception
+    /// has been taken, so the breakpoint handler sees `mepc` and `mcause` of the breakpoint,
+    /// not of the matched exception. Do not include the breakpoint cause 3 in the set.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct Etrigger {
+        causes: usize,
+        m: bool,
+        s: bool,
+        u: bool,
+    }
+
+    impl Etrigger {
+        /// Fire on exceptions whose cause N has bit N set in `causes`, in no privilege mode yet
+        #[inline]
+        pub const fn new(causes: usize) -> Self {
+            Self {
+                causes,
+                m: false,
+                s: false,
+                u: false,
+            }
+        }
+        /// Match exceptions taken from M-mode
+        #[inline]
+        pub const fn m(mut self) -> Self {
+            self.m = true;
+            self
+        }
+        /// Match exceptions taken from S-mode
+        #[inline]
+        pub const fn s(mut self) -> Self {
+            self.s = true;
+            self
+        }
+        /// Match exceptions taken from U-mode
+        #[inline]
+        pub const fn u(mut self) -> Self {
+            self.u = true;
+            self
+        }
+        /// Value of `tdata1` for trigger type [`TYPE_ETRIGGER`]
+        #[inline]
+        pub fn tdata1(&self) -> usize {
+            let mut bits = 0usize;
+            bits.set_bits(
+                usize::BITS as usize - 4..usize::BITS as usize,
+                TYPE_ETRIGGER,
+            );
+            bits.set_bit(9, self.m);
+            bits.set_bit(7, self.s);
+            bits.set_bit(6, self.u);
+            bits
+        }
+    }
+
+    /// Interrupt trigger configuration
+    ///
+    /// The trigger fires when an interrupt with a code in the given set is taken from an
+    /// enabled mode, raising a breakpoint exception as [`Etrigger`] does.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+    pub struct Itrigger {
+        interrupts: usize,
+        m: bool,
+        s: bool,
+        u: bool,
+        nmi: bool,
+    }
+
+    impl Itrigger {
+        /// Fire on interrupts whose code N has bit N set in `interrupts`, in no privilege mode yet
+        #[inline]
+        pub const fn new(interrupts: usize) -> Self {
+            Self {
+                interrupts,
+                m: false,
+                s: false,
+                u: false,
+                nmi: false,
+            }
+        }
+        /// Match interrupts taken from M-mode
+        #[inline]
+        pub const fn m(mut self) -> Self {
+            self.m = true;
+            self
+        }
+        /// Match interrupts taken from S-mode
+        #[inline]
+        pub const fn s(mut self) -> Self {
+            self.s = true;
+            self
+        }
+        /// Match interrupts taken from U-mode
+        #[inline]
+        pub const fn u(mut self) -> Self {
+            self.u = true;
+            self
+        }
+        /// Also match non-maskable interrupts, if supported by the trigger
+        #[inline]
+        pub const fn nmi(mut self) -> Self {
+            self.nmi = true;
+            self
+        }
+        /// Value of `tdata1` for trigger type [`TYPE_ITRIGGER`]
+        #[inline]
+        pub fn tdata1(&self) -> usize {
+            let mut bits = 0usize;
+            bits.set_bits(
+                usize::BITS as usize - 4..usize::BITS as usize,
+                TYPE_ITRIGGER,
+            );
+            bits.set_bit(10, self.nmi);
+            bits.set_bit(9, self.m);
+            bits.set_bit(7, self.s);
+            bits.set_bit(6, self.u);
+            bits
+        }
+    }
+
+    /// Arms trigger `index` as an exception trigger raising a breakpoint exception
+    ///
+    /// Returns false without arming if the trigger does not support type [`TYPE_ETRIGGER`].
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. An armed trigger raises breakpoint exceptions, which the M-mode trap
+    /// handler must be prepared to handle.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use sifive_core::register::trigger::{self, Etrigger};
+    ///
+    /// // break on load and store access faults during bring-up
+    /// unsafe { trigger::set_etrigger(0, Etrigger::new(1 << 5 | 1 << 7).m().s().u()) };
+    /// ```
+    #[inline]
+    pub unsafe fn set_etrigger(index: usize, config: Etrigger) -> bool {
+        select(index);
+        if supported_types() & (1 << TYPE_ETRIGGER) == 0 {
+            return false;
+        }
+        write_tdata1(0);
+        write_tdata2(config.causes);
+        write_tdata1(config.tdata1());
+        true
+    }
+
+    /// Arms trigger `index` as an interrupt trigger raising a breakpoint exception
+    ///
+    /// Returns false without arming if the trigger does not support type [`TYPE_ITRIGGER`].
+    ///
+    /// # Safety
+    ///
+    /// Must run on M mode. An armed trigger raises breakpoint exceptions, which the M-mode trap
+    /// handler must be prepared to handle.
+    #[inline]
+    pub unsafe fn set_itrigger(index: usize, config: Itrigger) -> bool {
+        select(index);
+        if supported_types() & (1 << TYPE_ITRIGGER) == 0 {
+            return false;
+        }
+        write_tdata1(0);
+        write_tdata2(config.interrupts);
+        write_tdata1(config.tdata1());
+        true
+    }
 }
@@ -0,0 +1,45 @@
+//! Cross-hart calls shared by per-hart register sequences
+
+/// Runs `local` on current hart while the other harts in `harts` run the same sequence
+///
+/// Sends an IPI with `send_ipi` to every hart in `harts` other than `current_hart`, runs
+/// `local`, and spins until `acked` reports each remote hart has finished. Returns the result
+/// of `local`.
+pub(crate) fn cross_call<R>(
+    current_hart: usize,
+    harts: &[usize],
+    send_ipi: impl Fn(usize),
+    acked: impl Fn(usize) -> bool,
+    local: impl FnOnce() -> R,
+) -> R {
+    let remote = || harts.iter().copied().filter(|&hart| hart != current_hart);
+    for hart in remote() {
+        send_ipi(hart);
+    }
+    let ans = local();
+    for hart in remote() {
+        while !acked(hart) {
+            core::hint::spin_loop();
+        }
+    }
+    ans
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cross_call;
+    use core::cell::Cell;
+
+    #[test]
+    fn signal_remote_harts_before_local_call() {
+        let sent = Cell::new(0u32);
+        let ans = cross_call(
+            1,
+            &[0, 1, 2, 3],
+            |hart| sent.set(sent.get() | 1 << hart),
+            |hart| sent.get() & 1 << hart != 0,
+            || sent.get(),
+        );
+        assert_eq!(ans, 0b1101);
+    }
+}